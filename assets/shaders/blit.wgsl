@@ -0,0 +1,26 @@
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+
+struct VertexOut {
+    @builtin(position) vertex_pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) v_index: u32) -> VertexOut {
+    // A triangle large enough to cover the whole screen.
+    let uv = vec2<f32>(f32((v_index << 1u) & 2u), f32(v_index & 2u));
+
+    var out: VertexOut;
+    out.vertex_pos = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    // Texture coordinates go down while clip space goes up.
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    return textureSample(source, source_sampler, in.uv);
+}
@@ -0,0 +1,25 @@
+// Block textures stored as the layers of a texture array.
+
+@group(0) @binding(1)
+var block_texture: texture_2d_array<f32>;
+@group(0) @binding(2)
+var block_sampler: sampler;
+@group(0) @binding(4)
+var block_normals: texture_2d_array<f32>;
+@group(0) @binding(5)
+var block_speculars: texture_2d_array<f32>;
+
+// Samples the block texture `texture_id`, repeating it outside of the [0, 1] range.
+fn sample_block(uv: vec2<f32>, texture_id: u32) -> vec4<f32> {
+    return textureSample(block_texture, block_sampler, uv, texture_id);
+}
+
+// Samples the normal map of `texture_id`, see `sample_block`.
+fn sample_block_normal(uv: vec2<f32>, texture_id: u32) -> vec4<f32> {
+    return textureSample(block_normals, block_sampler, uv, texture_id);
+}
+
+// Samples the specular map of `texture_id`, see `sample_block`.
+fn sample_block_specular(uv: vec2<f32>, texture_id: u32) -> vec4<f32> {
+    return textureSample(block_speculars, block_sampler, uv, texture_id);
+}
@@ -0,0 +1,47 @@
+// Block textures packed into the tiles of a single texture.
+
+@group(0) @binding(1)
+var block_texture: texture_2d<f32>;
+@group(0) @binding(2)
+var block_sampler: sampler;
+@group(0) @binding(4)
+var block_normals: texture_2d<f32>;
+@group(0) @binding(5)
+var block_speculars: texture_2d<f32>;
+
+struct TileCoords {
+    uv: vec2<f32>,
+    ddx: vec2<f32>,
+    ddy: vec2<f32>,
+}
+
+// Finds where `uv` is in the tile of `texture_id`, repeating it outside of the [0, 1] range.
+fn tile_coords(uv: vec2<f32>, texture_id: u32) -> TileCoords {
+    // Every tile is surrounded by padding in the atlas.
+    let cell_size = uniforms.tile_size + 2u * uniforms.tile_padding;
+    let tiles_per_row = uniforms.atlas_size / cell_size;
+    let tile = vec2<u32>(texture_id % tiles_per_row, texture_id / tiles_per_row);
+    let origin = vec2<f32>(tile * cell_size + uniforms.tile_padding) / f32(uniforms.atlas_size);
+    let scale = f32(uniforms.tile_size) / f32(uniforms.atlas_size);
+    // The wrapped coordinates jump at the edges of the tile, so the
+    // mipmap level is chosen from the derivatives of the original ones.
+    return TileCoords(origin + fract(uv) * scale, dpdx(uv) * scale, dpdy(uv) * scale);
+}
+
+// Samples the block texture `texture_id`, repeating it outside of the [0, 1] range.
+fn sample_block(uv: vec2<f32>, texture_id: u32) -> vec4<f32> {
+    let tile = tile_coords(uv, texture_id);
+    return textureSampleGrad(block_texture, block_sampler, tile.uv, tile.ddx, tile.ddy);
+}
+
+// Samples the normal map of `texture_id`, see `sample_block`.
+fn sample_block_normal(uv: vec2<f32>, texture_id: u32) -> vec4<f32> {
+    let tile = tile_coords(uv, texture_id);
+    return textureSampleGrad(block_normals, block_sampler, tile.uv, tile.ddx, tile.ddy);
+}
+
+// Samples the specular map of `texture_id`, see `sample_block`.
+fn sample_block_specular(uv: vec2<f32>, texture_id: u32) -> vec4<f32> {
+    let tile = tile_coords(uv, texture_id);
+    return textureSampleGrad(block_speculars, block_sampler, tile.uv, tile.ddx, tile.ddy);
+}
@@ -0,0 +1,33 @@
+struct Uniforms {
+    proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    inv_proj_view: mat4x4<f32>,
+    cam_pos: vec4<f32>,
+    sky_zenith: vec4<f32>,
+    sky_horizon: vec4<f32>,
+    sun_dir: vec4<f32>,
+    light_dir: vec4<f32>,
+    light_color: vec4<f32>,
+    ambient: vec4<f32>,
+    atlas_size: u32,
+    tile_size: u32,
+    fog_start: f32,
+    fog_end: f32,
+    tile_padding: u32,
+    time: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+const OUTLINE_COLOR = vec4<f32>(0.0, 0.0, 0.0, 0.6);
+
+@vertex
+fn vs_main(@location(0) pos: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return uniforms.proj * uniforms.view * vec4<f32>(pos, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return OUTLINE_COLOR;
+}
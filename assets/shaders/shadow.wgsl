@@ -0,0 +1,21 @@
+@group(0) @binding(0)
+var<uniform> light_proj_view: mat4x4<f32>;
+
+@group(1) @binding(0)
+var<uniform> chunk_offset: vec2<i32>;
+
+struct VertexIn {
+    @location(0) data: u32,
+    @location(1) attrs: u32,
+}
+
+@vertex
+fn vs_main(in: VertexIn) -> @builtin(position) vec4<f32> {
+    let x = in.data & 0x1f;
+    let y = (in.data >> 5u) & 0x1ff;
+    let z = (in.data >> 14u) & 0x1f;
+    var pos = vec3<f32>(f32(x), f32(y), f32(z));
+    pos.x += f32(chunk_offset.x * 16);
+    pos.z += f32(chunk_offset.y * 16);
+    return light_proj_view * vec4<f32>(pos, 1.0);
+}
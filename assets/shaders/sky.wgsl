@@ -0,0 +1,53 @@
+struct Uniforms {
+    proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    inv_proj_view: mat4x4<f32>,
+    cam_pos: vec4<f32>,
+    sky_zenith: vec4<f32>,
+    sky_horizon: vec4<f32>,
+    sun_dir: vec4<f32>,
+    light_dir: vec4<f32>,
+    light_color: vec4<f32>,
+    ambient: vec4<f32>,
+    atlas_size: u32,
+    tile_size: u32,
+    fog_start: f32,
+    fog_end: f32,
+    tile_padding: u32,
+    time: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+struct VertexOut {
+    @builtin(position) vertex_pos: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) v_index: u32) -> VertexOut {
+    // A triangle large enough to cover the whole screen.
+    let uv = vec2<f32>(f32((v_index << 1u) & 2u), f32(v_index & 2u));
+    let ndc = uv * 2.0 - 1.0;
+
+    var out: VertexOut;
+    out.vertex_pos = vec4<f32>(ndc, 0.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    let world = uniforms.inv_proj_view * vec4<f32>(in.ndc, 1.0, 1.0);
+    let dir = normalize(world.xyz / world.w - uniforms.cam_pos.xyz);
+
+    let height = clamp(dir.y, 0.0, 1.0);
+    var color = mix(uniforms.sky_horizon.rgb, uniforms.sky_zenith.rgb, pow(height, 0.6));
+
+    // Sun and moon discs.
+    let sun = smoothstep(0.9990, 0.9995, dot(dir, uniforms.sun_dir.xyz));
+    let moon = smoothstep(0.9994, 0.9997, dot(dir, -uniforms.sun_dir.xyz));
+    color += vec3<f32>(1.0, 0.9, 0.7) * sun + vec3<f32>(0.6, 0.65, 0.75) * moon;
+    return vec4<f32>(color, 1.0);
+}
@@ -1,54 +1,135 @@
 struct Uniforms {
     proj: mat4x4<f32>,
     view: mat4x4<f32>,
+    inv_proj_view: mat4x4<f32>,
+    cam_pos: vec4<f32>,
+    sky_zenith: vec4<f32>,
+    sky_horizon: vec4<f32>,
+    sun_dir: vec4<f32>,
+    light_dir: vec4<f32>,
+    light_color: vec4<f32>,
+    ambient: vec4<f32>,
     atlas_size: u32,
     tile_size: u32,
+    fog_start: f32,
+    fog_end: f32,
+    tile_padding: u32,
+    time: f32,
 }
 
 @group(0) @binding(0)
 var<uniform> uniforms: Uniforms;
 
+// The animation of every texture, four per element, see `Animation::pack`.
+@group(0) @binding(3)
+var<uniform> animations: array<vec4<u32>, 1024>;
+
 @group(1) @binding(0)
 var<uniform> chunk_offset: vec2<i32>;
 
+struct ShadowUniforms {
+    matrices: array<mat4x4<f32>, 3>,
+    splits: vec4<f32>,
+    cascade_count: u32,
+    texel_size: f32,
+}
+
+@group(2) @binding(0)
+var<uniform> shadow: ShadowUniforms;
+@group(2) @binding(1)
+var shadow_map: texture_depth_2d_array;
+@group(2) @binding(2)
+var shadow_sampler: sampler_comparison;
+
 struct VertexIn {
     @location(0) data: u32,
+    @location(1) attrs: u32,
     @builtin(vertex_index) v_index: u32
 }
 
 struct VertexOut {
     @builtin(position) vertex_pos: vec4<f32>,
-    @location(0) tex_coords: vec2<f32>
+    // Coordinates inside the block texture, repeating past 1.
+    // Centroid sampling keeps MSAA from reading texels outside of the face,
+    // which would bleed the neighbouring atlas tiles into its edges.
+    @location(0) @interpolate(perspective, centroid) tex_coords: vec2<f32>,
+    @location(1) world_pos: vec3<f32>,
+    @location(2) normal: vec3<f32>,
+    // Sky light and block light, from 0 to 1.
+    @location(3) light: vec2<f32>,
+    // The current frame of the texture, the next one and how far along it is.
+    @location(4) @interpolate(flat) texture_id: u32,
+    @location(5) @interpolate(flat) next_texture_id: u32,
+    @location(6) @interpolate(flat) frame_blend: f32,
+    // The `Face` the fragment belongs to.
+    @location(7) @interpolate(flat) face: u32,
 }
 
+// Indexed by `Face` (north, south, east, west, top, bottom).
+const FACE_NORMALS = array<vec3<f32>, 6>(
+    vec3<f32>(0.0, 0.0, 1.0),
+    vec3<f32>(0.0, 0.0, -1.0),
+    vec3<f32>(1.0, 0.0, 0.0),
+    vec3<f32>(-1.0, 0.0, 0.0),
+    vec3<f32>(0.0, 1.0, 0.0),
+    vec3<f32>(0.0, -1.0, 0.0),
+);
+
+// The directions the x axis and the top of the texture point to on every face, following
+// `QUAD_UVS` and the corners of `face_corners`. Used to orient normal maps.
+const FACE_TANGENTS = array<vec3<f32>, 6>(
+    vec3<f32>(-1.0, 0.0, 0.0),
+    vec3<f32>(1.0, 0.0, 0.0),
+    vec3<f32>(0.0, 0.0, 1.0),
+    vec3<f32>(0.0, 0.0, -1.0),
+    vec3<f32>(1.0, 0.0, 0.0),
+    vec3<f32>(1.0, 0.0, 0.0),
+);
+const FACE_BITANGENTS = array<vec3<f32>, 6>(
+    vec3<f32>(0.0, 1.0, 0.0),
+    vec3<f32>(0.0, 1.0, 0.0),
+    vec3<f32>(0.0, 1.0, 0.0),
+    vec3<f32>(0.0, 1.0, 0.0),
+    vec3<f32>(0.0, 0.0, 1.0),
+    vec3<f32>(0.0, 0.0, -1.0),
+);
+
+// Texture coordinates of the corners of a face, in the order they are meshed.
+const QUAD_UVS = array<vec2<f32>, 4>(
+    vec2<f32>(0.0, 0.0),
+    vec2<f32>(0.0, 1.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(1.0, 0.0),
+);
 
-fn calculate_texture_coordinates(v_index: u32, texture_id: u32) -> vec2<f32> {
-    let tile_width = uniforms.tile_size;
-    let tile_height = uniforms.tile_size;
-    let tiles_per_row = uniforms.atlas_size / tile_width;
-    let pixel_x = f32((texture_id % tiles_per_row) * tile_width);
-    let pixel_y = f32((texture_id / tiles_per_row) * tile_height);
-    switch (v_index % 4u) {
-          case 0u: {
-            // top left
-            return vec2<f32>(pixel_x / f32(uniforms.atlas_size), pixel_y / f32(uniforms.atlas_size));
-          }
-          case 1u: {
-            // bottom left
-            return vec2<f32>(pixel_x / f32(uniforms.atlas_size), (pixel_y + f32(tile_height)) / f32(uniforms.atlas_size));
-          }
-          case 2u: {
-            // bottom right
-            return vec2<f32>((pixel_x + f32(tile_width)) / f32(uniforms.atlas_size), (pixel_y + f32(tile_height)) / f32(uniforms.atlas_size));
-          }
-          case 3u: {
-            // top right
-            return vec2<f32>((pixel_x + f32(tile_width)) / f32(uniforms.atlas_size), pixel_y / f32(uniforms.atlas_size));
-          }
-          default: {
-              return vec2<f32>(0.0, 0.0);
-          }
-      }
+struct Frame {
+    texture_id: u32,
+    next_texture_id: u32,
+    blend: f32,
+}
+
+// Finds the current frame of a texture. The frames of an animated
+// texture have consecutive ids, starting with `texture_id`.
+fn animation_frame(texture_id: u32) -> Frame {
+    var frame = Frame(texture_id, texture_id, 0.0);
+    // `MAX_ANIMATED_TEXTURES`
+    if texture_id >= 4096u {
+        return frame;
+    }
+    let animation = animations[texture_id / 4u][texture_id % 4u];
+    let frames = animation & 0xffu;
+    if frames <= 1u {
+        return frame;
+    }
+    let frame_time = f32(animation >> 9u) / 1000.0;
+    let progress = uniforms.time / frame_time;
+    let index = u32(progress) % frames;
+    frame.texture_id = texture_id + index;
+    frame.next_texture_id = texture_id + (index + 1u) % frames;
+    if (animation & 0x100u) != 0u {
+        frame.blend = fract(progress);
+    }
+    return frame;
 }
 
 fn calculate_vertex_coordinates(data: u32) -> vec3<f32> {
@@ -66,18 +147,119 @@ fn vs_main(in: VertexIn) -> VertexOut{
     pos.z += f32(chunk_offset.y * 16);
 
     out.vertex_pos = uniforms.proj * uniforms.view * vec4<f32>(pos, 1.0);
+    out.world_pos = pos;
+    var normals = FACE_NORMALS;
+    out.face = in.attrs & 0x7u;
+    out.normal = normals[out.face];
+    out.light = vec2<f32>(f32((in.attrs >> 3u) & 0xfu), f32((in.attrs >> 7u) & 0xfu)) / 15.0;
 
-    let texture_id = (in.data >> 19u) & 0x1fff;
-    out.tex_coords = calculate_texture_coordinates(in.v_index, texture_id);
+    var uvs = QUAD_UVS;
+    out.tex_coords = uvs[in.v_index % 4u];
+    let frame = animation_frame((in.data >> 19u) & 0x1fff);
+    out.texture_id = frame.texture_id;
+    out.next_texture_id = frame.next_texture_id;
+    out.frame_blend = frame.blend;
     return out;
 }
 
-@group(0) @binding(1)
-var texture: texture_2d<f32>;
-@group(0) @binding(2)
-var texture_sampler: sampler;
+// `sample_block` and the block texture bindings are defined by
+// `block_atlas.wgsl` or `block_array.wgsl`, which are appended to this shader.
+
+const BLOCK_LIGHT_COLOR = vec3<f32>(1.0, 0.85, 0.6);
+const MIN_LIGHT = 0.02;
+
+// Returns how much of the sun light reaches a fragment, from 0 (shadowed) to 1 (lit).
+fn sun_visibility(world_pos: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let view_depth = (uniforms.view * vec4<f32>(world_pos, 1.0)).z;
+    if shadow.cascade_count == 0u || view_depth > shadow.splits[shadow.cascade_count - 1u] {
+        return 1.0;
+    }
+
+    var cascade = 0u;
+    for (var i = 0u; i < shadow.cascade_count; i++) {
+        if view_depth <= shadow.splits[i] {
+            cascade = i;
+            break;
+        }
+    }
+
+    // Offset along the normal to avoid faces shadowing themselves.
+    let offset_pos = world_pos + normal * 0.05 * f32(cascade + 1u);
+    let clip = shadow.matrices[cascade] * vec4<f32>(offset_pos, 1.0);
+    let uv = clip.xy * vec2<f32>(0.5, -0.5) + 0.5;
+
+    // Percentage closer filtering over a 3x3 area for softer edges.
+    var visibility = 0.0;
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            let texel = vec2<f32>(f32(x), f32(y)) * shadow.texel_size;
+            visibility += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + texel, cascade, clip.z);
+        }
+    }
+    return visibility / 9.0;
+}
+
+// Turns a normal map texel into a world space normal on the face `face`.
+fn surface_normal(texel: vec3<f32>, face: u32) -> vec3<f32> {
+    var normals = FACE_NORMALS;
+    var tangents = FACE_TANGENTS;
+    var bitangents = FACE_BITANGENTS;
+    let n = texel * 2.0 - 1.0;
+    return normalize(tangents[face] * n.x + bitangents[face] * n.y + normals[face] * n.z);
+}
+
+// Blinn-Phong highlight of the sun, scaled by the specular intensity in `specular.r`
+// and spread by the roughness in `specular.g`.
+fn sun_specular(normal: vec3<f32>, world_pos: vec3<f32>, specular: vec2<f32>) -> f32 {
+    let view_dir = normalize(uniforms.cam_pos.xyz - world_pos);
+    let half_dir = normalize(uniforms.light_dir.xyz + view_dir);
+    let shininess = exp2(10.0 * (1.0 - specular.y) + 1.0);
+    return specular.x * pow(max(dot(normal, half_dir), 0.0), shininess);
+}
+
+// Maps a light level to a brightness that drops off quickly in the dark.
+fn light_curve(level: f32) -> f32 {
+    return pow(0.8, (1.0 - level) * 15.0);
+}
 
 @fragment
 fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
-    return textureSample(texture, texture_sampler, in.tex_coords);
+    // Static textures sample the same frame twice, which is cheap, and keeps
+    // the sampling in uniform control flow.
+    let albedo = mix(
+        sample_block(in.tex_coords, in.texture_id),
+        sample_block(in.tex_coords, in.next_texture_id),
+        in.frame_blend,
+    );
+
+    let normal_texel = mix(
+        sample_block_normal(in.tex_coords, in.texture_id),
+        sample_block_normal(in.tex_coords, in.next_texture_id),
+        in.frame_blend,
+    );
+    let specular = mix(
+        sample_block_specular(in.tex_coords, in.texture_id),
+        sample_block_specular(in.tex_coords, in.next_texture_id),
+        in.frame_blend,
+    );
+    let normal = surface_normal(normal_texel.xyz, in.face);
+
+    // Shadows follow the face, normal maps can't light faces turned away from the sun.
+    var sun_light = 0.0;
+    if dot(in.normal, uniforms.light_dir.xyz) > 0.0 {
+        sun_light = sun_visibility(in.world_pos, in.normal);
+    }
+    let diffuse = max(dot(normal, uniforms.light_dir.xyz), 0.0) * sun_light;
+    let sun = uniforms.ambient.rgb + uniforms.light_color.rgb * diffuse;
+    let sky_light = light_curve(in.light.x) * step(0.001, in.light.x);
+    let block_light = light_curve(in.light.y) * step(0.001, in.light.y);
+    let light = max(sun * sky_light + BLOCK_LIGHT_COLOR * block_light, vec3<f32>(MIN_LIGHT));
+    let highlight = uniforms.light_color.rgb
+        * sun_specular(normal, in.world_pos, specular.xy) * sun_light * sky_light;
+    let color = vec4<f32>(albedo.rgb * light + highlight, albedo.a);
+
+    // Fade distant terrain into the horizon so the edge of the loaded chunks is not visible.
+    let distance = length(in.world_pos.xz - uniforms.cam_pos.xz);
+    let fog = smoothstep(uniforms.fog_start, uniforms.fog_end, distance);
+    return vec4<f32>(mix(color.rgb, uniforms.sky_horizon.rgb, fog), color.a);
 }
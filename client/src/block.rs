@@ -1,6 +1,6 @@
 use std::{collections::HashMap, path::Path};
 
-use common::block::BlockId;
+use common::{block::BlockId, face::Face};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -20,6 +20,20 @@ pub struct BlockTextures {
     pub south: String,
 }
 
+impl BlockTextures {
+    /// The name of the texture used for the given face.
+    pub fn get(&self, face: Face) -> &str {
+        match face {
+            Face::North => &self.north,
+            Face::South => &self.south,
+            Face::East => &self.east,
+            Face::West => &self.west,
+            Face::Top => &self.top,
+            Face::Bottom => &self.bottom,
+        }
+    }
+}
+
 impl BlockSettings {}
 
 pub struct BlockMap {
@@ -1,10 +1,101 @@
 use std::f32;
 
 use common::chunk::Chunk;
+use serde::{Deserialize, Serialize};
 use vek::{Mat4, Vec2, Vec3};
 
 const NEAR_PLANE: f32 = 0.1;
-const FAR_PLANE: f32 = 1000.0;
+/// How far behind or in front of the player the third person cameras are.
+const THIRD_PERSON_DISTANCE: f32 = 4.0;
+/// How far from the player the orbit camera is.
+const ORBIT_DISTANCE: f32 = 6.0;
+
+/// The field of view never goes past these, whatever the effects, in radians.
+const MIN_FOV: f32 = 0.02;
+const MAX_FOV: f32 = 2.9;
+/// How far screen shake moves the view at most, in blocks.
+const MAX_SHAKE_OFFSET: f32 = 0.15;
+/// How much of the screen shake fades away every second, from 0 to 1.
+const SHAKE_DECAY: f32 = 1.5;
+/// How far the player walks during one head bob, in blocks.
+const BOB_STRIDE: f32 = 1.4;
+/// How far head bob moves the view down and sideways, in blocks.
+const BOB_HEIGHT: f32 = 0.06;
+const BOB_SWAY: f32 = 0.03;
+
+/// How the field of view and the position of the view react to what the player does.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+    /// Multiplies the field of view while zooming.
+    pub zoom_fov_scale: f32,
+    /// Multiplies the field of view while sprinting.
+    pub sprint_fov_scale: f32,
+    /// How long the field of view takes to change, in seconds. 0 changes it immediately.
+    pub fov_smoothing: f32,
+    /// How much the view bobs while walking, 0 disables it.
+    pub head_bob: f32,
+    /// How much the view shakes, for example after a long fall. 0 disables it.
+    pub screen_shake: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            zoom_fov_scale: 0.3,
+            sprint_fov_scale: 1.15,
+            fov_smoothing: 0.1,
+            head_bob: 1.0,
+            screen_shake: 1.0,
+        }
+    }
+}
+
+impl CameraSettings {
+    pub fn validated(self) -> Self {
+        let defaults = Self::default();
+        let valid = |value: f32, default: f32, min: f32, max: f32| {
+            if value.is_finite() {
+                value.clamp(min, max)
+            } else {
+                default
+            }
+        };
+        Self {
+            zoom_fov_scale: valid(self.zoom_fov_scale, defaults.zoom_fov_scale, 0.05, 1.0),
+            sprint_fov_scale: valid(self.sprint_fov_scale, defaults.sprint_fov_scale, 1.0, 1.5),
+            fov_smoothing: valid(self.fov_smoothing, defaults.fov_smoothing, 0.0, 1.0),
+            head_bob: valid(self.head_bob, defaults.head_bob, 0.0, 2.0),
+            screen_shake: valid(self.screen_shake, defaults.screen_shake, 0.0, 2.0),
+        }
+    }
+}
+
+/// Where the camera looks from, relative to the eyes of the player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CameraMode {
+    /// From the eyes of the player.
+    #[default]
+    FirstPerson,
+    /// Behind the player, looking the same way.
+    ThirdPersonBack,
+    /// In front of the player, looking back at it.
+    ThirdPersonFront,
+    /// Turns around the player without changing where it looks, to inspect its surroundings.
+    Orbit,
+}
+
+impl CameraMode {
+    /// The mode after this one, going back to first person after the last one.
+    pub fn next(self) -> Self {
+        match self {
+            Self::FirstPerson => Self::ThirdPersonBack,
+            Self::ThirdPersonBack => Self::ThirdPersonFront,
+            Self::ThirdPersonFront => Self::Orbit,
+            Self::Orbit => Self::FirstPerson,
+        }
+    }
+}
 
 pub struct Matrices {
     pub proj: Mat4<f32>,
@@ -12,11 +103,26 @@ pub struct Matrices {
 }
 
 pub struct Camera {
+    /// The eyes of the player, which the camera follows.
     pos: Vec3<f32>,
+    /// Where the player looks, as yaw and pitch in radians.
     rotation: Vec2<f32>,
+    mode: CameraMode,
+    /// Where the orbit camera looks from, independently of the player.
+    orbit_rotation: Vec2<f32>,
+    /// How far the view is from `pos`, shortened when terrain is in the way.
+    view_distance: f32,
+    /// Moves the view for effects such as screen shake, in world space.
+    offset: Vec3<f32>,
     aspect: f32,
     matrices: Matrices,
-    fov: f32,
+    /// The vertical field of view from the settings, in radians.
+    base_fov: f32,
+    /// Multiplies `base_fov`, animated towards `target_fov_scale`.
+    fov_scale: f32,
+    target_fov_scale: f32,
+    /// How long the field of view takes to change, in seconds.
+    fov_smoothing: f32,
 }
 
 impl Camera {
@@ -24,15 +130,17 @@ impl Camera {
         Self {
             pos: Vec3::new(0.0, Chunk::SIZE.y as f32 + 2f32, -2.0),
             rotation: Vec2::new(-1.5, 0.0),
+            mode: CameraMode::default(),
+            orbit_rotation: Vec2::zero(),
+            view_distance: 0.0,
+            offset: Vec3::zero(),
             aspect,
-            fov: f32::consts::FRAC_PI_2,
+            base_fov: f32::consts::FRAC_PI_2,
+            fov_scale: 1.0,
+            target_fov_scale: 1.0,
+            fov_smoothing: 0.0,
             matrices: Matrices {
-                proj: Mat4::perspective_lh_no(
-                    f32::consts::FRAC_PI_2,
-                    aspect,
-                    NEAR_PLANE,
-                    FAR_PLANE,
-                ),
+                proj: perspective_reversed_infinite(f32::consts::FRAC_PI_2, aspect, NEAR_PLANE),
                 view: Mat4::identity(),
             },
         }
@@ -40,44 +148,263 @@ impl Camera {
 
     pub fn set_aspect_ratio(&mut self, aspect: f32) {
         self.aspect = aspect;
-        self.matrices.proj = Mat4::perspective_lh_no(self.fov, aspect, NEAR_PLANE, FAR_PLANE);
+        self.update_projection();
     }
 
+    /// Sets the vertical field of view, in radians, before any zoom.
+    pub fn set_fov(&mut self, fov: f32) {
+        self.base_fov = fov;
+        self.update_projection();
+    }
+
+    /// Zooms in or out smoothly, by multiplying the field of view with `scale`.
+    pub fn set_fov_scale(&mut self, scale: f32) {
+        self.target_fov_scale = scale;
+    }
+
+    /// Sets how long the field of view takes to change, in seconds.
+    pub fn set_fov_smoothing(&mut self, seconds: f32) {
+        self.fov_smoothing = seconds;
+    }
+
+    /// Moves the view by `offset` for effects such as screen shake, without moving the player.
+    pub fn set_offset(&mut self, offset: Vec3<f32>) {
+        self.offset = offset;
+    }
+
+    /// Animates the field of view over `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        if self.fov_scale == self.target_fov_scale {
+            return;
+        }
+        self.fov_scale = if self.fov_smoothing > 0.0 {
+            let t = 1.0 - (-dt / self.fov_smoothing).exp();
+            let scale = self.fov_scale + (self.target_fov_scale - self.fov_scale) * t;
+            // Snap when close enough, so the projection stops being recomputed.
+            if (scale - self.target_fov_scale).abs() < 1e-4 {
+                self.target_fov_scale
+            } else {
+                scale
+            }
+        } else {
+            self.target_fov_scale
+        };
+        self.update_projection();
+    }
+
+    fn update_projection(&mut self) {
+        self.matrices.proj = perspective_reversed_infinite(self.fov(), self.aspect, NEAR_PLANE);
+    }
+
+    /// Turns by `dx` degrees to the right and `dy` degrees down.
+    ///
+    /// In orbit mode the camera turns around the player, which keeps looking the same way.
     pub fn rotate_by(&mut self, dx: f32, dy: f32) {
-        self.rotation.x += dx.to_radians();
-        self.rotation.y += -dy.to_radians();
-        self.rotation.y = self.rotation.y.clamp(
+        let rotation = match self.mode {
+            CameraMode::Orbit => &mut self.orbit_rotation,
+            _ => &mut self.rotation,
+        };
+        rotation.x += dx.to_radians();
+        rotation.y += -dy.to_radians();
+        rotation.y = rotation.y.clamp(
             -f32::consts::FRAC_PI_2 + 0.0001,
             f32::consts::FRAC_PI_2 - 0.0001,
         );
     }
 
+    pub fn mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    /// Switches to another mode. The orbit camera starts behind the player.
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        if mode == CameraMode::Orbit && self.mode != CameraMode::Orbit {
+            self.orbit_rotation = self.rotation;
+        }
+        self.mode = mode;
+        self.view_distance = self.max_view_distance();
+    }
+
+    /// How far the view is from the eyes of the player when nothing is in the way.
+    pub fn max_view_distance(&self) -> f32 {
+        match self.mode {
+            CameraMode::FirstPerson => 0.0,
+            CameraMode::ThirdPersonBack | CameraMode::ThirdPersonFront => THIRD_PERSON_DISTANCE,
+            CameraMode::Orbit => ORBIT_DISTANCE,
+        }
+    }
+
+    /// Moves the view closer to the eyes of the player, for example so that it
+    /// stays out of the terrain. It never goes further than [`Camera::max_view_distance`].
+    pub fn set_view_distance(&mut self, distance: f32) {
+        self.view_distance = distance.clamp(0.0, self.max_view_distance());
+    }
+
+    /// The direction the view looks in.
+    pub fn view_dir(&self) -> Vec3<f32> {
+        match self.mode {
+            CameraMode::FirstPerson | CameraMode::ThirdPersonBack => self.forward(),
+            CameraMode::ThirdPersonFront => -self.forward(),
+            CameraMode::Orbit => direction(self.orbit_rotation),
+        }
+    }
+
+    /// Where the view is, which is behind the eyes of the player in the third person modes.
+    pub fn view_pos(&self) -> Vec3<f32> {
+        self.pos - self.view_dir() * self.view_distance + self.offset
+    }
+
     pub fn compute_matrices(&mut self) -> Matrices {
-        self.matrices.view = Mat4::look_at_lh(self.pos, self.pos + self.forward(), Vec3::unit_y());
+        let view_pos = self.view_pos();
+        self.matrices.view = Mat4::look_at_lh(view_pos, view_pos + self.view_dir(), Vec3::unit_y());
         Matrices {
             proj: self.matrices.proj,
             view: self.matrices.view,
         }
     }
 
-    pub fn move_by(&mut self, dx: f32, dy: f32, dz: f32) {
-        self.pos += dz * self.forward_xz() + -dx * self.right() + Vec3::unit_y() * dy;
+    /// The vertical field of view used this frame, in radians.
+    pub fn fov(&self) -> f32 {
+        (self.base_fov * self.fov_scale).clamp(MIN_FOV, MAX_FOV)
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.aspect
+    }
+
+    pub fn near_plane(&self) -> f32 {
+        NEAR_PLANE
+    }
+
+    /// The eyes of the player, see [`Camera::view_pos`] for where the view is.
+    pub fn pos(&self) -> Vec3<f32> {
+        self.pos
+    }
+
+    pub fn set_pos(&mut self, pos: Vec3<f32>) {
+        self.pos = pos;
+    }
+
+    /// Turns a direction relative to the camera, where x is right, y is up and z is
+    /// forward along the ground, into world space.
+    pub fn to_world(&self, dir: Vec3<f32>) -> Vec3<f32> {
+        dir.z * self.forward_xz() + -dir.x * self.right() + Vec3::unit_y() * dir.y
     }
 
     pub fn right(&self) -> Vec3<f32> {
         self.forward().cross(Vec3::unit_y()).normalized()
     }
 
+    /// Where the player looks, which is not where the view looks in every mode.
     pub fn forward(&self) -> Vec3<f32> {
-        Vec3::new(
-            f32::cos(self.rotation.x) * f32::cos(self.rotation.y),
-            f32::sin(self.rotation.y),
-            -f32::sin(self.rotation.x) * f32::cos(self.rotation.y),
-        )
-        .normalized()
+        direction(self.rotation)
     }
 
     pub fn forward_xz(&self) -> Vec3<f32> {
         Vec3::new(f32::cos(self.rotation.x), 0.0, -f32::sin(self.rotation.x)).normalized()
     }
 }
+
+/// A left-handed perspective projection with reversed depth and no far plane.
+///
+/// Depth goes from 1 at the near plane to 0 infinitely far away. Floats are most
+/// precise close to 0, which balances out the precision lost with the distance,
+/// so far away terrain doesn't z-fight. Depth tests have to use `Greater`.
+#[rustfmt::skip]
+fn perspective_reversed_infinite(fov_y: f32, aspect: f32, near: f32) -> Mat4<f32> {
+    let f = 1.0 / (fov_y / 2.0).tan();
+    Mat4::new(
+        f / aspect, 0.0, 0.0, 0.0,
+        0.0, f, 0.0, 0.0,
+        0.0, 0.0, 0.0, near,
+        0.0, 0.0, 1.0, 0.0,
+    )
+}
+
+/// The direction pointed at by a yaw and a pitch, in radians.
+fn direction(rotation: Vec2<f32>) -> Vec3<f32> {
+    Vec3::new(
+        f32::cos(rotation.x) * f32::cos(rotation.y),
+        f32::sin(rotation.y),
+        -f32::sin(rotation.x) * f32::cos(rotation.y),
+    )
+    .normalized()
+}
+
+/// Moves the view around the eyes of the player, to shake it or make it bob while walking.
+#[derive(Debug, Default)]
+pub struct CameraEffects {
+    /// How strong the screen shake is, from 0 to 1. The shake grows with its square.
+    trauma: f32,
+    /// Seconds since the effects started, to move the shake around.
+    time: f32,
+    /// How far into a stride the player is, in radians.
+    bob_phase: f32,
+    /// How much the view bobs, from 0 when standing still to 1 when walking.
+    bob_strength: f32,
+}
+
+impl CameraEffects {
+    /// Shakes the view by `amount`, from 0 to 1. Shakes add up, and fade away over time.
+    pub fn shake(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+
+    /// Advances the effects by `dt` seconds, while the player walks on the ground
+    /// at `walk_speed` blocks per second.
+    pub fn update(&mut self, dt: f32, walk_speed: f32) {
+        self.time += dt;
+        self.trauma = (self.trauma - SHAKE_DECAY * dt).max(0.0);
+        self.bob_phase =
+            (self.bob_phase + walk_speed * dt / BOB_STRIDE * f32::consts::PI) % f32::consts::TAU;
+        let walking = if walk_speed > 0.1 { 1.0 } else { 0.0 };
+        self.bob_strength += (walking - self.bob_strength) * (1.0 - (-10.0 * dt).exp());
+    }
+
+    /// How far the effects move the view. `right` points to the right of the view,
+    /// and `bob` is whether the view should bob at all.
+    pub fn offset(&self, settings: &CameraSettings, right: Vec3<f32>, bob: bool) -> Vec3<f32> {
+        // Sines of unrelated frequencies add up to something that looks random.
+        let noise = |seed: f32| {
+            let t = self.time * 20.0 + seed;
+            (t.sin() + (t * 2.3 + 1.7).sin() * 0.5) / 1.5
+        };
+        let shake = Vec3::new(noise(0.0), noise(10.0), noise(20.0))
+            * self.trauma.powi(2)
+            * MAX_SHAKE_OFFSET
+            * settings.screen_shake;
+        if !bob {
+            return shake;
+        }
+        let strength = self.bob_strength * settings.head_bob;
+        let sway = right * self.bob_phase.sin() * BOB_SWAY;
+        let height = -Vec3::unit_y() * self.bob_phase.sin().powi(2) * BOB_HEIGHT;
+        shake + (sway + height) * strength
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vek::{Vec3, Vec4};
+
+    use super::perspective_reversed_infinite;
+
+    #[test]
+    fn reversed_depth_test() {
+        let proj = perspective_reversed_infinite(std::f32::consts::FRAC_PI_2, 2.0, 0.1);
+        let depth = |z: f32| {
+            let clip = proj * Vec4::from_point(Vec3::new(0.0, 0.0, z));
+            clip.z / clip.w
+        };
+        assert!((depth(0.1) - 1.0).abs() < 1e-6);
+        // Closer is always deeper, even very far away.
+        assert!(depth(1.0) > depth(10.0));
+        assert!(depth(10_000.0) > depth(100_000.0));
+        assert!(depth(100_000.0) > 0.0);
+
+        // The field of view and aspect ratio are kept.
+        let clip = proj * Vec4::new(1.0, 1.0, 1.0, 1.0);
+        assert!((clip.x / clip.w - 0.5).abs() < 1e-6);
+        assert!((clip.y / clip.w - 1.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,99 @@
+use gilrs::{Axis, Button, EventType, Gilrs};
+
+use crate::input::{GamepadAxis, GamepadButton};
+
+/// Something that happened on one of the connected gamepads.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GamepadEvent {
+    Button(GamepadButton, bool),
+    Axis(GamepadAxis, f32),
+    /// A gamepad was disconnected, anything it held down should be released.
+    Disconnected,
+}
+
+/// Reads the input of every connected gamepad.
+pub struct Gamepads {
+    gilrs: Gilrs,
+}
+
+impl Gamepads {
+    /// Starts listening to gamepads, or returns `None` if gamepads are not supported.
+    pub fn new() -> Option<Self> {
+        match Gilrs::new() {
+            Ok(gilrs) => {
+                for (_, gamepad) in gilrs.gamepads() {
+                    tracing::info!("Gamepad connected: {}", gamepad.name());
+                }
+                Some(Self { gilrs })
+            }
+            Err(e) => {
+                tracing::warn!("Gamepads are not available: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Takes the next event that happened since the last call.
+    pub fn next_event(&mut self) -> Option<GamepadEvent> {
+        while let Some(event) = self.gilrs.next_event() {
+            let event = match event.event {
+                EventType::ButtonPressed(button, _) => {
+                    button_from_gilrs(button).map(|b| GamepadEvent::Button(b, true))
+                }
+                EventType::ButtonReleased(button, _) => {
+                    button_from_gilrs(button).map(|b| GamepadEvent::Button(b, false))
+                }
+                EventType::AxisChanged(axis, value, _) => {
+                    axis_from_gilrs(axis).map(|a| GamepadEvent::Axis(a, value))
+                }
+                EventType::Connected => {
+                    let gamepad = self.gilrs.gamepad(event.id);
+                    tracing::info!("Gamepad connected: {}", gamepad.name());
+                    None
+                }
+                EventType::Disconnected => {
+                    tracing::info!("Gamepad disconnected");
+                    Some(GamepadEvent::Disconnected)
+                }
+                _ => None,
+            };
+            if event.is_some() {
+                return event;
+            }
+        }
+        None
+    }
+}
+
+fn button_from_gilrs(button: Button) -> Option<GamepadButton> {
+    Some(match button {
+        Button::South => GamepadButton::South,
+        Button::East => GamepadButton::East,
+        Button::North => GamepadButton::North,
+        Button::West => GamepadButton::West,
+        Button::LeftTrigger => GamepadButton::LeftTrigger,
+        Button::LeftTrigger2 => GamepadButton::LeftTrigger2,
+        Button::RightTrigger => GamepadButton::RightTrigger,
+        Button::RightTrigger2 => GamepadButton::RightTrigger2,
+        Button::Select => GamepadButton::Select,
+        Button::Start => GamepadButton::Start,
+        Button::Mode => GamepadButton::Mode,
+        Button::LeftThumb => GamepadButton::LeftThumb,
+        Button::RightThumb => GamepadButton::RightThumb,
+        Button::DPadUp => GamepadButton::DPadUp,
+        Button::DPadDown => GamepadButton::DPadDown,
+        Button::DPadLeft => GamepadButton::DPadLeft,
+        Button::DPadRight => GamepadButton::DPadRight,
+        _ => return None,
+    })
+}
+
+fn axis_from_gilrs(axis: Axis) -> Option<GamepadAxis> {
+    Some(match axis {
+        Axis::LeftStickX => GamepadAxis::LeftStickX,
+        Axis::LeftStickY => GamepadAxis::LeftStickY,
+        Axis::RightStickX => GamepadAxis::RightStickX,
+        Axis::RightStickY => GamepadAxis::RightStickY,
+        _ => return None,
+    })
+}
@@ -0,0 +1,929 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    path::Path,
+    str::FromStr,
+};
+
+use serde::{de::IntoDeserializer, Deserialize, Serialize};
+use vek::{Vec2, Vec3};
+use winit::{event::MouseButton, keyboard::KeyCode};
+
+/// The key bindings file loaded at startup, relative to the working directory.
+pub const INPUT_PATH: &str = "input.toml";
+
+/// The bindings changed in game, relative to the working directory. They replace
+/// the bindings of the same actions in [`INPUT_PATH`].
+pub const INPUT_OVERRIDES_PATH: &str = "input.user.toml";
+
+/// Something the player can do, bound to keys or mouse buttons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Sneak,
+    /// Moves faster while held down.
+    Sprint,
+    /// Moves slower while held down, for precise positioning.
+    MoveSlowly,
+    /// Switches between walking and flying.
+    ToggleFly,
+    /// Switches between the first person, third person and orbit cameras.
+    CycleCamera,
+    /// Narrows the field of view while held down.
+    Zoom,
+    Break,
+    Place,
+    SelectBlock1,
+    SelectBlock2,
+    SelectBlock3,
+    SelectBlock4,
+    PauseTime,
+    SlowDownTime,
+    SpeedUpTime,
+    ReloadSettings,
+    /// Rebinds an action: press this, then a binding of the action, then its new binding.
+    Rebind,
+    /// Switches between the window and fullscreen.
+    ToggleFullscreen,
+    ToggleMenu,
+}
+
+impl Action {
+    pub const ALL: [Action; 24] = [
+        Action::MoveForward,
+        Action::MoveBackward,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Jump,
+        Action::Sneak,
+        Action::Sprint,
+        Action::MoveSlowly,
+        Action::ToggleFly,
+        Action::CycleCamera,
+        Action::Zoom,
+        Action::Break,
+        Action::Place,
+        Action::SelectBlock1,
+        Action::SelectBlock2,
+        Action::SelectBlock3,
+        Action::SelectBlock4,
+        Action::PauseTime,
+        Action::SlowDownTime,
+        Action::SpeedUpTime,
+        Action::ReloadSettings,
+        Action::Rebind,
+        Action::ToggleFullscreen,
+        Action::ToggleMenu,
+    ];
+
+    /// The actions selecting a block to place, in the order of [`PLACEABLE_BLOCKS`](crate::scene::PLACEABLE_BLOCKS).
+    pub const SELECT_BLOCK: [Action; 4] = [
+        Action::SelectBlock1,
+        Action::SelectBlock2,
+        Action::SelectBlock3,
+        Action::SelectBlock4,
+    ];
+
+    fn default_bindings(self) -> Vec<Binding> {
+        use Binding::{Gamepad, Key, Mouse};
+        match self {
+            Action::MoveForward => vec![Key(KeyCode::KeyW)],
+            Action::MoveBackward => vec![Key(KeyCode::KeyS)],
+            Action::MoveLeft => vec![Key(KeyCode::KeyA)],
+            Action::MoveRight => vec![Key(KeyCode::KeyD)],
+            Action::Jump => vec![Key(KeyCode::Space), Gamepad(GamepadButton::South)],
+            Action::Sneak => vec![Key(KeyCode::ShiftLeft), Gamepad(GamepadButton::East)],
+            Action::Sprint => vec![Key(KeyCode::ControlLeft), Gamepad(GamepadButton::LeftThumb)],
+            Action::MoveSlowly => vec![Key(KeyCode::AltLeft)],
+            Action::ToggleFly => vec![Key(KeyCode::KeyF), Gamepad(GamepadButton::North)],
+            Action::CycleCamera => vec![Key(KeyCode::KeyV), Gamepad(GamepadButton::Select)],
+            Action::Zoom => vec![Key(KeyCode::KeyC), Gamepad(GamepadButton::RightThumb)],
+            Action::Break => vec![
+                Mouse(MouseButton::Left),
+                Gamepad(GamepadButton::RightTrigger2),
+            ],
+            Action::Place => vec![
+                Mouse(MouseButton::Right),
+                Gamepad(GamepadButton::LeftTrigger2),
+            ],
+            Action::SelectBlock1 => vec![Key(KeyCode::Digit1), Gamepad(GamepadButton::DPadUp)],
+            Action::SelectBlock2 => vec![Key(KeyCode::Digit2), Gamepad(GamepadButton::DPadRight)],
+            Action::SelectBlock3 => vec![Key(KeyCode::Digit3), Gamepad(GamepadButton::DPadDown)],
+            Action::SelectBlock4 => vec![Key(KeyCode::Digit4), Gamepad(GamepadButton::DPadLeft)],
+            Action::PauseTime => vec![Key(KeyCode::KeyP)],
+            Action::SlowDownTime => vec![Key(KeyCode::BracketLeft)],
+            Action::SpeedUpTime => vec![Key(KeyCode::BracketRight)],
+            Action::ReloadSettings => vec![Key(KeyCode::F5)],
+            Action::Rebind => vec![Key(KeyCode::F6)],
+            Action::ToggleFullscreen => vec![Key(KeyCode::F11)],
+            Action::ToggleMenu => vec![Key(KeyCode::Escape), Gamepad(GamepadButton::Start)],
+        }
+    }
+}
+
+/// A gamepad button, named after its position on the gamepad.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GamepadButton {
+    /// The bottom face button, A on Xbox controllers.
+    South,
+    /// The right face button, B on Xbox controllers.
+    East,
+    /// The top face button, Y on Xbox controllers.
+    North,
+    /// The left face button, X on Xbox controllers.
+    West,
+    LeftTrigger,
+    LeftTrigger2,
+    RightTrigger,
+    RightTrigger2,
+    Select,
+    Start,
+    Mode,
+    LeftThumb,
+    RightThumb,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+/// An analogue stick axis, from -1 to 1. Up and right are positive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+}
+
+/// A key, a mouse button or a gamepad button.
+///
+/// Written as the name of the key, such as `"KeyW"` or `"Space"`, as
+/// `"MouseLeft"`, `"MouseRight"`, `"MouseMiddle"`, `"MouseBack"`, `"MouseForward"`
+/// and `"Mouse<number>"` for other mouse buttons, or as `"Gamepad"` followed
+/// by a [`GamepadButton`], such as `"GamepadSouth"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton),
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // The names of the variants are also how they are deserialized.
+            Binding::Key(code) => write!(f, "{:?}", code),
+            Binding::Mouse(MouseButton::Left) => write!(f, "MouseLeft"),
+            Binding::Mouse(MouseButton::Right) => write!(f, "MouseRight"),
+            Binding::Mouse(MouseButton::Middle) => write!(f, "MouseMiddle"),
+            Binding::Mouse(MouseButton::Back) => write!(f, "MouseBack"),
+            Binding::Mouse(MouseButton::Forward) => write!(f, "MouseForward"),
+            Binding::Mouse(MouseButton::Other(button)) => write!(f, "Mouse{}", button),
+            Binding::Gamepad(button) => write!(f, "Gamepad{:?}", button),
+        }
+    }
+}
+
+impl FromStr for Binding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let button = match s {
+            "MouseLeft" => Some(MouseButton::Left),
+            "MouseRight" => Some(MouseButton::Right),
+            "MouseMiddle" => Some(MouseButton::Middle),
+            "MouseBack" => Some(MouseButton::Back),
+            "MouseForward" => Some(MouseButton::Forward),
+            _ => s
+                .strip_prefix("Mouse")
+                .and_then(|button| button.parse().ok())
+                .map(MouseButton::Other),
+        };
+        if let Some(button) = button {
+            return Ok(Binding::Mouse(button));
+        }
+        let unknown = |_: serde::de::value::Error| format!("unknown key or button `{}`", s);
+        if let Some(button) = s.strip_prefix("Gamepad") {
+            return GamepadButton::deserialize(button.into_deserializer())
+                .map(Binding::Gamepad)
+                .map_err(unknown);
+        }
+        KeyCode::deserialize(s.into_deserializer())
+            .map(Binding::Key)
+            .map_err(unknown)
+    }
+}
+
+impl TryFrom<String> for Binding {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Binding> for String {
+    fn from(value: Binding) -> Self {
+        value.to_string()
+    }
+}
+
+/// How fast the player walks and flies around.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MovementSettings {
+    /// The speed when no modifier is held down, in blocks per second.
+    pub speed: f32,
+    /// Multiplies the speed while [`Action::Sprint`] is held down.
+    pub sprint_multiplier: f32,
+    /// Multiplies the speed while [`Action::MoveSlowly`] is held down.
+    pub slow_multiplier: f32,
+    /// How fast the player speeds up, in blocks per second squared.
+    pub acceleration: f32,
+    /// How fast the player slows down when no movement key is held down,
+    /// in blocks per second squared.
+    pub deceleration: f32,
+}
+
+impl Default for MovementSettings {
+    fn default() -> Self {
+        Self {
+            speed: 7.0,
+            sprint_multiplier: 3.0,
+            slow_multiplier: 0.25,
+            acceleration: 50.0,
+            deceleration: 35.0,
+        }
+    }
+}
+
+impl MovementSettings {
+    fn validated(self) -> Self {
+        let defaults = Self::default();
+        let positive = |value: f32, default: f32| {
+            if value.is_finite() && value > 0.0 {
+                value
+            } else {
+                default
+            }
+        };
+        Self {
+            speed: positive(self.speed, defaults.speed),
+            sprint_multiplier: positive(self.sprint_multiplier, defaults.sprint_multiplier),
+            slow_multiplier: positive(self.slow_multiplier, defaults.slow_multiplier),
+            acceleration: positive(self.acceleration, defaults.acceleration),
+            deceleration: positive(self.deceleration, defaults.deceleration),
+        }
+    }
+}
+
+/// How analogue sticks move the player and the camera.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GamepadSettings {
+    /// How far sticks have to be pushed before they do anything, from 0 to 1.
+    /// Sticks rarely rest exactly in the centre.
+    pub dead_zone: f32,
+    /// The exponent applied to how far sticks are pushed past the dead zone. Values
+    /// above 1 give finer control near the centre, 1 is linear.
+    pub response_curve: f32,
+    /// How fast the camera turns with the right stick pushed all the way, in degrees per second.
+    pub look_speed: f32,
+}
+
+impl Default for GamepadSettings {
+    fn default() -> Self {
+        Self {
+            dead_zone: 0.15,
+            response_curve: 2.0,
+            look_speed: 180.0,
+        }
+    }
+}
+
+impl GamepadSettings {
+    /// Applies the dead zone and the response curve to the position of a stick.
+    ///
+    /// The dead zone is circular, and the rest of the range is stretched so the
+    /// output still goes smoothly from 0 to 1.
+    pub fn stick(&self, stick: Vec2<f32>) -> Vec2<f32> {
+        let length = stick.magnitude();
+        if length <= self.dead_zone || length == 0.0 {
+            return Vec2::zero();
+        }
+        let strength = ((length - self.dead_zone) / (1.0 - self.dead_zone)).min(1.0);
+        stick / length * strength.powf(self.response_curve)
+    }
+
+    fn validated(mut self) -> Self {
+        let defaults = Self::default();
+        if !self.dead_zone.is_finite() {
+            self.dead_zone = defaults.dead_zone;
+        }
+        self.dead_zone = self.dead_zone.clamp(0.0, 0.9);
+        if !self.response_curve.is_finite() {
+            self.response_curve = defaults.response_curve;
+        }
+        self.response_curve = self.response_curve.clamp(0.1, 10.0);
+        if !self.look_speed.is_finite() {
+            self.look_speed = defaults.look_speed;
+        }
+        self.look_speed = self.look_speed.max(0.0);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputSettings {
+    /// Multiplies how far the camera turns when the mouse moves.
+    pub mouse_sensitivity: f32,
+    /// How long the camera takes to catch up with the mouse, in seconds. 0 turns
+    /// it immediately.
+    pub mouse_smoothing: f32,
+    /// Whether moving the mouse or the right stick up looks down.
+    pub invert_y: bool,
+    pub movement: MovementSettings,
+    pub gamepad: GamepadSettings,
+    /// The keys and mouse buttons bound to every action.
+    ///
+    /// Actions that are not listed use their default binding,
+    /// an empty list leaves the action unbound.
+    pub bindings: BTreeMap<Action, Vec<Binding>>,
+}
+
+impl Default for InputSettings {
+    fn default() -> Self {
+        Self {
+            mouse_sensitivity: 1.0,
+            mouse_smoothing: 0.0,
+            invert_y: false,
+            movement: MovementSettings::default(),
+            gamepad: GamepadSettings::default(),
+            bindings: Action::ALL
+                .into_iter()
+                .map(|action| (action, action.default_bindings()))
+                .collect(),
+        }
+    }
+}
+
+/// The bindings of the actions rebound in game, saved apart from the bindings file
+/// so that it is never rewritten.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BindingOverrides {
+    pub bindings: BTreeMap<Action, Vec<Binding>>,
+}
+
+impl BindingOverrides {
+    /// Loads the overrides from a TOML file, or returns no overrides if there is none.
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        match std::fs::read_to_string(path) {
+            Ok(file) => toml::from_str(&file).unwrap_or_else(|e| {
+                tracing::error!("Invalid input file `{}`: {}", path.display(), e);
+                Self::default()
+            }),
+            // Nothing was rebound yet.
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Writes the overrides to a TOML file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let file = toml::to_string_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(path, file)
+    }
+}
+
+impl InputSettings {
+    pub const MIN_MOUSE_SENSITIVITY: f32 = 0.01;
+    pub const MAX_MOUSE_SENSITIVITY: f32 = 10.0;
+    pub const MAX_MOUSE_SMOOTHING: f32 = 1.0;
+
+    /// Loads the bindings from a TOML file, with the bindings rebound in game from
+    /// the `overrides` file replacing its own.
+    ///
+    /// If the file can not be read or parsed, the error is logged and the default
+    /// bindings are used. Conflicting bindings are logged, and trigger every action
+    /// they are bound to.
+    pub fn load<P: AsRef<Path>, Q: AsRef<Path>>(path: P, overrides: Q) -> Self {
+        let mut settings = Self::read(path.as_ref());
+        settings.apply_overrides(BindingOverrides::load(overrides));
+        let settings = settings.validated();
+        for (binding, actions) in settings.conflicts() {
+            tracing::warn!("`{}` is bound to several actions: {:?}", binding, actions);
+        }
+        tracing::info!(?settings, "Loaded input settings");
+        settings
+    }
+
+    /// Reads a bindings file as it is, without validating it.
+    fn read(path: &Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(file) => match toml::from_str::<InputSettings>(&file) {
+                Ok(settings) => settings,
+                Err(e) => {
+                    tracing::error!("Invalid input file `{}`: {}", path.display(), e);
+                    Self::default()
+                }
+            },
+            Err(e) => {
+                tracing::info!(
+                    "Could not read `{}` ({}), using the default bindings",
+                    path.display(),
+                    e
+                );
+                Self::default()
+            }
+        }
+    }
+
+    /// Replaces the bindings of the actions in `overrides`.
+    pub fn apply_overrides(&mut self, overrides: BindingOverrides) {
+        self.bindings.extend(overrides.bindings);
+    }
+
+    /// The bindings that differ from the ones in `base`.
+    pub fn overrides(&self, base: &InputSettings) -> BindingOverrides {
+        BindingOverrides {
+            bindings: self
+                .bindings
+                .iter()
+                .filter(|(action, bindings)| base.bindings.get(action) != Some(bindings))
+                .map(|(&action, bindings)| (action, bindings.clone()))
+                .collect(),
+        }
+    }
+
+    /// Saves the bindings that differ from the bindings file at `path` to the
+    /// `overrides` file, leaving the bindings file untouched.
+    pub fn save_overrides<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        path: P,
+        overrides: Q,
+    ) -> std::io::Result<()> {
+        let base = Self::read(path.as_ref()).validated();
+        self.overrides(&base).save(overrides)
+    }
+
+    /// How far the camera turns when the mouse moves by `delta`, in degrees.
+    ///
+    /// Positive x turns right and positive y looks down.
+    pub fn mouse_look(&self, delta: Vec2<f32>) -> Vec2<f32> {
+        self.invert_look(delta * DEGREES_PER_MOUSE_UNIT * self.mouse_sensitivity)
+    }
+
+    fn invert_look(&self, delta: Vec2<f32>) -> Vec2<f32> {
+        if self.invert_y {
+            Vec2::new(delta.x, -delta.y)
+        } else {
+            delta
+        }
+    }
+
+    /// Binds the missing actions to their default binding, and clamps the sensitivity.
+    pub fn validated(mut self) -> Self {
+        let defaults = Self::default();
+        for (action, bindings) in defaults.bindings {
+            self.bindings.entry(action).or_insert(bindings);
+        }
+        if !self.mouse_sensitivity.is_finite() {
+            self.mouse_sensitivity = defaults.mouse_sensitivity;
+        }
+        self.mouse_sensitivity = self
+            .mouse_sensitivity
+            .clamp(Self::MIN_MOUSE_SENSITIVITY, Self::MAX_MOUSE_SENSITIVITY);
+        if !self.mouse_smoothing.is_finite() {
+            self.mouse_smoothing = defaults.mouse_smoothing;
+        }
+        self.mouse_smoothing = self.mouse_smoothing.clamp(0.0, Self::MAX_MOUSE_SMOOTHING);
+        self.movement = self.movement.validated();
+        self.gamepad = self.gamepad.validated();
+        self
+    }
+
+    /// The actions bound to `binding`.
+    pub fn actions(&self, binding: Binding) -> impl Iterator<Item = Action> + '_ {
+        self.bindings
+            .iter()
+            .filter(move |(_, bindings)| bindings.contains(&binding))
+            .map(|(&action, _)| action)
+    }
+
+    /// Every binding used by more than one action, with these actions.
+    pub fn conflicts(&self) -> Vec<(Binding, Vec<Action>)> {
+        let bindings = self
+            .bindings
+            .values()
+            .flatten()
+            .copied()
+            .collect::<HashSet<_>>();
+        let mut conflicts = bindings
+            .into_iter()
+            .map(|binding| (binding, self.actions(binding).collect::<Vec<_>>()))
+            .filter(|(_, actions)| actions.len() > 1)
+            .collect::<Vec<_>>();
+        conflicts.sort_by_key(|(_, actions)| actions.clone());
+        conflicts
+    }
+
+    /// Replaces the binding `old` of `action` with `binding`, keeping its other
+    /// bindings. Without `old`, or if `action` isn't bound to it, `binding` is added.
+    ///
+    /// The binding is removed from any other action, to avoid conflicts.
+    /// Returns the actions that lost it.
+    pub fn rebind(
+        &mut self,
+        action: Action,
+        old: Option<Binding>,
+        binding: Binding,
+    ) -> Vec<Action> {
+        let mut unbound = vec![];
+        for (&other, bindings) in &mut self.bindings {
+            if other != action && bindings.contains(&binding) {
+                bindings.retain(|&b| b != binding);
+                unbound.push(other);
+            }
+        }
+        let bindings = self.bindings.entry(action).or_default();
+        bindings.retain(|&b| Some(b) == old || b != binding);
+        match old.and_then(|old| bindings.iter().position(|&b| b == old)) {
+            Some(index) => bindings[index] = binding,
+            None => bindings.push(binding),
+        }
+        unbound
+    }
+}
+
+/// How far the camera turns when the mouse moves by one unit, in degrees.
+const DEGREES_PER_MOUSE_UNIT: f32 = 0.1;
+
+/// Tracks which actions are held down, and where the gamepad sticks are.
+#[derive(Debug, Default)]
+pub struct InputState {
+    pressed: HashSet<Binding>,
+    left_stick: Vec2<f32>,
+    right_stick: Vec2<f32>,
+}
+
+impl InputState {
+    /// Records that `binding` was pressed or released.
+    ///
+    /// Returns the actions that were just triggered, which is none
+    /// when it was released or is repeated while held down.
+    pub fn update(
+        &mut self,
+        settings: &InputSettings,
+        binding: Binding,
+        pressed: bool,
+    ) -> Vec<Action> {
+        let triggered = if pressed {
+            self.pressed.insert(binding)
+        } else {
+            self.pressed.remove(&binding);
+            false
+        };
+        if triggered {
+            settings.actions(binding).collect()
+        } else {
+            vec![]
+        }
+    }
+
+    /// Whether `binding` is held down.
+    pub fn is_held(&self, binding: Binding) -> bool {
+        self.pressed.contains(&binding)
+    }
+
+    /// Records the position of a gamepad stick.
+    pub fn set_axis(&mut self, axis: GamepadAxis, value: f32) {
+        let value = value.clamp(-1.0, 1.0);
+        match axis {
+            GamepadAxis::LeftStickX => self.left_stick.x = value,
+            GamepadAxis::LeftStickY => self.left_stick.y = value,
+            GamepadAxis::RightStickX => self.right_stick.x = value,
+            GamepadAxis::RightStickY => self.right_stick.y = value,
+        }
+    }
+
+    /// Releases everything, for example when the window loses the focus.
+    pub fn clear(&mut self) {
+        self.pressed.clear();
+        self.left_stick = Vec2::zero();
+        self.right_stick = Vec2::zero();
+    }
+
+    /// Releases the gamepad buttons and centres the sticks, for example when a
+    /// gamepad is disconnected. Keys and mouse buttons stay held down.
+    pub fn clear_gamepad(&mut self) {
+        self.pressed
+            .retain(|binding| !matches!(binding, Binding::Gamepad(_)));
+        self.left_stick = Vec2::zero();
+        self.right_stick = Vec2::zero();
+    }
+
+    /// Whether any of the bindings of `action` is held down.
+    pub fn is_pressed(&self, settings: &InputSettings, action: Action) -> bool {
+        settings
+            .bindings
+            .get(&action)
+            .is_some_and(|bindings| bindings.iter().any(|b| self.pressed.contains(b)))
+    }
+
+    /// The direction the player wants to move in, relative to the camera:
+    /// x is right, y is up and z is forward. The horizontal part is at most 1 long.
+    pub fn movement_dir(&self, settings: &InputSettings) -> Vec3<f32> {
+        let axis = |positive: Action, negative: Action| {
+            self.is_pressed(settings, positive) as u8 as f32
+                - self.is_pressed(settings, negative) as u8 as f32
+        };
+        let stick = settings.gamepad.stick(self.left_stick);
+        let mut horizontal = Vec2::new(
+            axis(Action::MoveRight, Action::MoveLeft) + stick.x,
+            axis(Action::MoveForward, Action::MoveBackward) + stick.y,
+        );
+        // Moving diagonally is not faster, but a stick pushed halfway is slower.
+        if horizontal.magnitude_squared() > 1.0 {
+            horizontal.normalize();
+        }
+        Vec3::new(
+            horizontal.x,
+            axis(Action::Jump, Action::Sneak),
+            horizontal.y,
+        )
+    }
+
+    /// The speed the player wants to move at, in blocks per second.
+    pub fn move_speed(&self, settings: &InputSettings) -> f32 {
+        let movement = &settings.movement;
+        // Holding both cancels out.
+        match (
+            self.is_pressed(settings, Action::Sprint),
+            self.is_pressed(settings, Action::MoveSlowly),
+        ) {
+            (true, false) => movement.speed * movement.sprint_multiplier,
+            (false, true) => movement.speed * movement.slow_multiplier,
+            _ => movement.speed,
+        }
+    }
+
+    /// How far the right stick turns the camera over `dt` seconds, in degrees,
+    /// see [`InputSettings::mouse_look`].
+    pub fn look_delta(&self, settings: &InputSettings, dt: f32) -> Vec2<f32> {
+        let stick = settings.gamepad.stick(self.right_stick);
+        // Pushing the stick up looks up, like moving the mouse away.
+        let delta = Vec2::new(stick.x, -stick.y) * settings.gamepad.look_speed * dt;
+        settings.invert_look(delta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vek::{Vec2, Vec3};
+    use winit::{event::MouseButton, keyboard::KeyCode};
+
+    use super::{
+        Action, Binding, BindingOverrides, GamepadAxis, GamepadButton, GamepadSettings,
+        InputSettings, InputState,
+    };
+
+    #[test]
+    fn parse_bindings_test() {
+        let settings = toml::from_str::<InputSettings>(
+            r#"
+            mouse_sensitivity = 2.0
+            [bindings]
+            move_forward = ["ArrowUp", "KeyW"]
+            jump = ["Mouse5"]
+            place = ["MouseMiddle", "GamepadRightThumb"]
+            break = []
+            "#,
+        )
+        .unwrap()
+        .validated();
+        assert_eq!(settings.mouse_sensitivity, 2.0);
+        assert_eq!(
+            settings.bindings[&Action::MoveForward],
+            [Binding::Key(KeyCode::ArrowUp), Binding::Key(KeyCode::KeyW)]
+        );
+        assert_eq!(
+            settings.bindings[&Action::Jump],
+            [Binding::Mouse(MouseButton::Other(5))]
+        );
+        assert_eq!(
+            settings.bindings[&Action::Place],
+            [
+                Binding::Mouse(MouseButton::Middle),
+                Binding::Gamepad(GamepadButton::RightThumb)
+            ]
+        );
+        assert!(settings.bindings[&Action::Break].is_empty());
+        // Missing actions use their default binding.
+        assert_eq!(
+            settings.bindings[&Action::Sneak],
+            [
+                Binding::Key(KeyCode::ShiftLeft),
+                Binding::Gamepad(GamepadButton::East)
+            ]
+        );
+
+        assert!(toml::from_str::<InputSettings>("[bindings]\njump = [\"NotAKey\"]").is_err());
+
+        // Bindings are written the way they are read.
+        let file = toml::to_string(&settings).unwrap();
+        assert_eq!(toml::from_str::<InputSettings>(&file).unwrap(), settings);
+    }
+
+    #[test]
+    fn conflicts_test() {
+        let mut settings = InputSettings::default();
+        assert!(settings.conflicts().is_empty());
+
+        settings
+            .bindings
+            .get_mut(&Action::Jump)
+            .unwrap()
+            .push(Binding::Key(KeyCode::KeyW));
+        assert_eq!(
+            settings.conflicts(),
+            [(
+                Binding::Key(KeyCode::KeyW),
+                vec![Action::MoveForward, Action::Jump]
+            )]
+        );
+
+        // Rebinding takes the binding away from the other actions, and keeps the
+        // other bindings of the rebound action.
+        let unbound = settings.rebind(
+            Action::Sneak,
+            Some(Binding::Key(KeyCode::ShiftLeft)),
+            Binding::Key(KeyCode::KeyW),
+        );
+        assert_eq!(unbound, [Action::MoveForward, Action::Jump]);
+        assert!(settings.conflicts().is_empty());
+        assert_eq!(
+            settings.bindings[&Action::Sneak],
+            [
+                Binding::Key(KeyCode::KeyW),
+                Binding::Gamepad(GamepadButton::East)
+            ]
+        );
+
+        // Without a binding to replace, the new one is added once.
+        for _ in 0..2 {
+            settings.rebind(Action::Sneak, None, Binding::Key(KeyCode::KeyC));
+        }
+        assert_eq!(
+            settings.bindings[&Action::Sneak],
+            [
+                Binding::Key(KeyCode::KeyW),
+                Binding::Gamepad(GamepadButton::East),
+                Binding::Key(KeyCode::KeyC)
+            ]
+        );
+        assert_eq!(
+            settings.bindings[&Action::Jump],
+            [
+                Binding::Key(KeyCode::Space),
+                Binding::Gamepad(GamepadButton::South)
+            ]
+        );
+    }
+
+    #[test]
+    fn input_state_test() {
+        let mut settings = InputSettings::default();
+        settings.bindings.insert(
+            Action::MoveForward,
+            vec![Binding::Key(KeyCode::KeyW), Binding::Key(KeyCode::ArrowUp)],
+        );
+        let mut state = InputState::default();
+        let w = Binding::Key(KeyCode::KeyW);
+        let up = Binding::Key(KeyCode::ArrowUp);
+
+        assert_eq!(state.update(&settings, w, true), [Action::MoveForward]);
+        // Repeated presses don't trigger the action again.
+        assert!(state.update(&settings, w, true).is_empty());
+        state.update(&settings, up, true);
+        state.update(&settings, w, false);
+        // Still held down with the other binding.
+        assert!(state.is_pressed(&settings, Action::MoveForward));
+        assert_eq!(state.movement_dir(&settings), Vec3::new(0.0, 0.0, 1.0));
+
+        state.clear();
+        assert_eq!(state.movement_dir(&settings), Vec3::zero());
+    }
+
+    #[test]
+    fn gamepad_stick_test() {
+        let gamepad = GamepadSettings {
+            dead_zone: 0.2,
+            response_curve: 2.0,
+            look_speed: 90.0,
+        };
+        assert_eq!(gamepad.stick(Vec2::new(0.1, -0.1)), Vec2::zero());
+        assert_eq!(gamepad.stick(Vec2::new(0.0, 1.0)), Vec2::new(0.0, 1.0));
+        // Halfway between the dead zone and the edge, squared by the curve.
+        let stick = gamepad.stick(Vec2::new(-0.6, 0.0));
+        assert!((stick.x + 0.25).abs() < 1e-5);
+        // Diagonals don't go past the edge.
+        assert!(gamepad.stick(Vec2::new(1.0, 1.0)).magnitude() <= 1.0 + 1e-5);
+
+        let settings = InputSettings {
+            gamepad,
+            ..Default::default()
+        };
+        let mut state = InputState::default();
+        state.set_axis(GamepadAxis::LeftStickY, 1.0);
+        state.set_axis(GamepadAxis::RightStickX, 1.0);
+        // Keys and sticks together still move at full speed.
+        state.update(&settings, Binding::Key(KeyCode::KeyW), true);
+        assert_eq!(state.movement_dir(&settings), Vec3::new(0.0, 0.0, 1.0));
+        // A second of 90 degrees per second.
+        assert_eq!(state.look_delta(&settings, 1.0), Vec2::new(90.0, 0.0));
+
+        // Disconnecting the gamepad keeps the keys held down.
+        state.update(&settings, Binding::Gamepad(GamepadButton::South), true);
+        state.clear_gamepad();
+        assert_eq!(state.look_delta(&settings, 1.0), Vec2::zero());
+        assert!(!state.is_pressed(&settings, Action::Jump));
+        assert_eq!(state.movement_dir(&settings), Vec3::new(0.0, 0.0, 1.0));
+
+        state.clear();
+        assert_eq!(state.movement_dir(&settings), Vec3::zero());
+    }
+
+    #[test]
+    fn movement_test() {
+        let mut settings = InputSettings::default();
+        let mut state = InputState::default();
+        let press = |state: &mut InputState, settings: &InputSettings, key| {
+            state.update(settings, Binding::Key(key), true);
+        };
+        press(&mut state, &settings, KeyCode::KeyW);
+        press(&mut state, &settings, KeyCode::KeyD);
+        // Diagonals are as fast as straight lines.
+        let dir = state.movement_dir(&settings);
+        assert!((dir.magnitude() - 1.0).abs() < 1e-5);
+        assert!((dir.x - dir.z).abs() < 1e-5);
+
+        let speed = settings.movement.speed;
+        assert_eq!(state.move_speed(&settings), speed);
+        press(&mut state, &settings, KeyCode::ControlLeft);
+        assert_eq!(
+            state.move_speed(&settings),
+            speed * settings.movement.sprint_multiplier
+        );
+        press(&mut state, &settings, KeyCode::AltLeft);
+        assert_eq!(state.move_speed(&settings), speed);
+
+        assert_eq!(
+            settings.mouse_look(Vec2::new(10.0, 20.0)),
+            Vec2::new(1.0, 2.0)
+        );
+        settings.invert_y = true;
+        assert_eq!(
+            settings.mouse_look(Vec2::new(10.0, 20.0)),
+            Vec2::new(1.0, -2.0)
+        );
+    }
+
+    #[test]
+    fn binding_overrides_test() {
+        let base = InputSettings::default();
+        let mut settings = base.clone();
+        settings.rebind(
+            Action::PauseTime,
+            Some(Binding::Key(KeyCode::KeyP)),
+            Binding::Key(KeyCode::KeyO),
+        );
+        // Only the rebound actions are saved.
+        let overrides = settings.overrides(&base);
+        assert_eq!(
+            overrides.bindings,
+            [(Action::PauseTime, vec![Binding::Key(KeyCode::KeyO)])].into()
+        );
+
+        let file = toml::to_string_pretty(&overrides).unwrap();
+        let mut loaded = base.clone();
+        loaded.apply_overrides(toml::from_str::<BindingOverrides>(&file).unwrap());
+        assert_eq!(loaded, settings);
+    }
+
+    #[test]
+    fn default_bindings_file_test() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../input.toml");
+        let file = std::fs::read_to_string(path).unwrap();
+        let settings = toml::from_str::<InputSettings>(&file).unwrap();
+        assert_eq!(settings, InputSettings::default());
+    }
+}
@@ -1,6 +1,9 @@
 pub mod block;
 pub mod camera;
-pub mod key_state;
+#[cfg(feature = "gamepad")]
+pub mod gamepad;
+pub mod input;
 pub mod render;
 pub mod scene;
+pub mod settings;
 pub mod window;
@@ -1,8 +1,37 @@
-use client::window::Window;
+use client::{settings::parse_resolution, window::Window};
+
+const USAGE: &str = "Usage: client [--resolution <width>x<height>]";
 
 fn main() {
     tracing_subscriber::fmt::init();
-    let mut window = Window::new();
+    let mut resolution = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-r" | "--resolution" => match args.next().as_deref().and_then(parse_resolution) {
+                Some(value) => resolution = Some(value),
+                None => exit_with_usage("--resolution expects a size such as 1920x1080"),
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => exit_with_usage(&format!("Unknown argument `{}`", arg)),
+        }
+    }
+
+    let mut window = match Window::new(resolution) {
+        Ok(window) => window,
+        Err(e) => {
+            tracing::error!("Failed to start the renderer: {}", e);
+            std::process::exit(1);
+        }
+    };
     window.grab_cursor(true);
     window.run();
 }
+
+fn exit_with_usage(error: &str) -> ! {
+    eprintln!("{}\n{}", error, USAGE);
+    std::process::exit(2);
+}
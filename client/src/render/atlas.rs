@@ -1,93 +1,799 @@
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::{Path, PathBuf},
+};
 
-use image::{GenericImage, RgbaImage};
+use image::{imageops::FilterType, GenericImageView, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+/// How block textures are stored on the GPU.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockTextureMode {
+    /// Every texture is a tile of a single large texture.
+    Atlas,
+    /// Every texture is a layer of a texture array. Filtering and mipmaps never
+    /// blend textures together, and the number of textures is only limited by
+    /// the maximum number of layers.
+    #[default]
+    Array,
+}
+
+impl BlockTextureMode {
+    pub fn view_dimension(self) -> wgpu::TextureViewDimension {
+        match self {
+            BlockTextureMode::Atlas => wgpu::TextureViewDimension::D2,
+            BlockTextureMode::Array => wgpu::TextureViewDimension::D2Array,
+        }
+    }
+}
+
+/// The images every block texture is made of.
+///
+/// Normal and specular maps are optional, and are read from images next to
+/// the texture with the `_n` and `_s` suffixes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureMap {
+    /// The color of the texture.
+    Albedo,
+    /// The direction of the surface, relative to the face: red points along
+    /// the texture's x axis, green towards its top, and blue out of the face.
+    Normal,
+    /// How shiny the surface is: red is the specular intensity, green the roughness.
+    Specular,
+}
+
+impl TextureMap {
+    const COMPANIONS: [TextureMap; 2] = [TextureMap::Normal, TextureMap::Specular];
+
+    /// The suffix of the file name of the map, after the texture name.
+    fn suffix(self) -> &'static str {
+        match self {
+            TextureMap::Albedo => "",
+            TextureMap::Normal => "_n",
+            TextureMap::Specular => "_s",
+        }
+    }
+
+    /// Whether the map holds sRGB colors, rather than linear values.
+    pub fn is_srgb(self) -> bool {
+        self == TextureMap::Albedo
+    }
+
+    pub fn format(self) -> wgpu::TextureFormat {
+        if self.is_srgb() {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        }
+    }
+
+    /// The value used by textures that don't have this map:
+    /// a flat surface without any specular highlight.
+    fn default_texel(self) -> Rgba<u8> {
+        match self {
+            TextureMap::Albedo => Rgba([255, 255, 255, 255]),
+            TextureMap::Normal => Rgba([128, 128, 255, 255]),
+            TextureMap::Specular => Rgba([0, 255, 0, 255]),
+        }
+    }
+
+    /// The map a file name refers to, and the name of its texture.
+    fn from_name(name: &str) -> (TextureMap, &str) {
+        for map in Self::COMPANIONS {
+            if let Some(texture) = name.strip_suffix(map.suffix()) {
+                return (map, texture);
+            }
+        }
+        (TextureMap::Albedo, name)
+    }
+}
+
+/// Textures with an id past this one can't be animated.
+pub const MAX_ANIMATED_TEXTURES: usize = 4096;
+
+/// How an animated texture cycles through its frames.
+///
+/// Read from a TOML file next to the texture, with the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Animation {
+    /// The number of seconds every frame is shown for.
+    pub frame_time: f32,
+    /// Blend every frame into the next one, instead of switching between them.
+    pub interpolate: bool,
+    /// The number of frames, given by the height of the texture.
+    #[serde(skip)]
+    pub frames: u32,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Self {
+            frame_time: 0.1,
+            interpolate: false,
+            frames: 1,
+        }
+    }
+}
+
+impl Animation {
+    /// Loads the animation settings of a texture with `frames` frames.
+    ///
+    /// The default settings are used if the file does not exist or is not valid.
+    fn load(path: impl AsRef<Path>, frames: u32) -> Self {
+        let path = path.as_ref();
+        let animation = match std::fs::read_to_string(path) {
+            Ok(file) => toml::from_str::<Animation>(&file).unwrap_or_else(|e| {
+                tracing::error!("Invalid animation `{}`: {}", path.display(), e);
+                Animation::default()
+            }),
+            Err(_) => Animation::default(),
+        };
+        Self {
+            frames,
+            ..animation
+        }
+    }
+
+    /// Packs the animation for the terrain shader: the number of frames in the
+    /// lowest 8 bits, then the interpolation flag, then the frame time in milliseconds.
+    pub fn pack(self) -> u32 {
+        let frame_time = (self.frame_time * 1000.0)
+            .round()
+            .clamp(1.0, (1 << 23) as f32 - 1.0);
+        self.frames.min(0xff) | (self.interpolate as u32) << 8 | (frame_time as u32) << 9
+    }
+}
+
+/// The texture used for texture names that don't exist.
+pub const MISSING_TEXTURE_ID: u32 = 0;
+
+/// Errors that can happen while loading the block textures.
+#[derive(Debug)]
+pub enum AtlasError {
+    /// The texture directory could not be read.
+    ReadDir {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// None of the texture directories contain a texture that could be loaded.
+    NoTextures(Vec<PathBuf>),
+}
+
+impl fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AtlasError::ReadDir { path, source } => write!(
+                f,
+                "Could not read the texture directory `{}`: {}",
+                path.display(),
+                source
+            ),
+            AtlasError::NoTextures(paths) => write!(
+                f,
+                "The texture directories {:?} do not contain any PNG texture",
+                paths
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AtlasError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AtlasError::ReadDir { source, .. } => Some(source),
+            AtlasError::NoTextures(_) => None,
+        }
+    }
+}
 
 pub struct BlockAtlas {
     pub buf: RgbaImage,
     pub size: u32,
     pub tile_size: u32,
+    /// Texels around every tile filled with the edges of the tile, so that
+    /// filtering and mipmapping don't blend neighbouring tiles together.
+    pub padding: u32,
+    /// The number of mipmap levels, including the full size atlas.
+    pub mip_levels: u32,
+    /// Every texture, indexed by texture id.
+    pub tiles: Vec<RgbaImage>,
+    /// The normal map of every texture, indexed by texture id.
+    pub normal_tiles: Vec<RgbaImage>,
+    /// The specular map of every texture, indexed by texture id.
+    pub specular_tiles: Vec<RgbaImage>,
     pub texture_map: HashMap<String, u32>,
+    /// Animated textures, by the id of their first frame. The
+    /// other frames use the ids that follow it.
+    pub animations: HashMap<u32, Animation>,
 }
 
 impl BlockAtlas {
-    pub fn new<P: AsRef<Path>>(resource_path: P) -> Self {
-        let files = std::fs::read_dir(&resource_path)
-            .unwrap()
-            .map(|x| x.map(|x| x.path()))
-            // filter out anything that does not contain a png
-            .filter(|x| {
-                x.as_ref()
-                    .unwrap()
-                    .extension()
-                    .map(|x| x == "png")
-                    .unwrap_or(false)
+    /// Loads every PNG texture in the `roots` directories.
+    ///
+    /// A texture in a root replaces the textures with the same file name in the
+    /// roots before it, so resource packs can override some of the base textures.
+    /// The maps of a replaced texture are dropped, unless a later root provides them.
+    /// Textures are given ids in the alphabetical order of their file names, so
+    /// ids don't change between runs. The id [`MISSING_TEXTURE_ID`] is a
+    /// generated checkerboard. Textures that can't be read are skipped.
+    ///
+    /// Textures can have different resolutions, they are all scaled
+    /// to the size of the largest one.
+    ///
+    /// Every texture can have a normal and a specular map, see [`TextureMap`].
+    pub fn new<P: AsRef<Path>>(roots: &[P]) -> Result<Self, AtlasError> {
+        // Sorted by name, `read_dir` does not guarantee any order. Every file is
+        // stored with the index of its root.
+        let mut files = BTreeMap::new();
+        for (index, root) in roots.iter().enumerate() {
+            let root = root.as_ref();
+            let dir = std::fs::read_dir(root).map_err(|source| AtlasError::ReadDir {
+                path: root.to_owned(),
+                source,
+            })?;
+            for entry in dir {
+                let path = match entry {
+                    Ok(entry) => entry.path(),
+                    Err(e) => {
+                        tracing::error!("Failed to read texture directory entry: {}", e);
+                        continue;
+                    }
+                };
+                // filter out anything that is not a png
+                if path.extension().is_none_or(|x| x != "png") {
+                    continue;
+                }
+                let Some(name) = path.file_stem().and_then(|x| x.to_str()) else {
+                    tracing::warn!("Ignoring texture with a non UTF-8 name: {}", path.display());
+                    continue;
+                };
+                if let Some((_, previous)) = files.insert(name.to_owned(), (index, path.clone())) {
+                    tracing::info!(
+                        "Texture `{}` overrides `{}`",
+                        path.display(),
+                        previous.display()
+                    );
+                }
+            }
+        }
+
+        // The maps of a texture overridden by a later root were made for the old one.
+        let outdated = files
+            .iter()
+            .filter(|(name, (root, _))| match TextureMap::from_name(name) {
+                (TextureMap::Albedo, _) => false,
+                (_, texture) => files
+                    .get(texture)
+                    .is_some_and(|(texture_root, _)| texture_root > root),
             })
-            .collect::<Result<Vec<_>, _>>()
-            .unwrap();
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        for name in outdated {
+            if let Some((_, file)) = files.remove(&name) {
+                tracing::info!(
+                    "Ignoring `{}`, its texture is overridden by a later root",
+                    file.display()
+                );
+            }
+        }
+        let files = files
+            .into_iter()
+            .map(|(name, (_, file))| (name, file))
+            .collect::<BTreeMap<_, _>>();
 
         tracing::info!(?files);
 
+        let textures = files
+            .iter()
+            .filter(|(name, file)| match TextureMap::from_name(name) {
+                (TextureMap::Albedo, _) => true,
+                (_, texture) => {
+                    if !files.contains_key(texture) {
+                        tracing::warn!(
+                            "Ignoring `{}`, there is no texture named `{}`",
+                            file.display(),
+                            texture
+                        );
+                    }
+                    false
+                }
+            })
+            .filter_map(|(name, file)| {
+                let texture = match image::open(file) {
+                    Ok(texture) => texture,
+                    Err(e) => {
+                        tracing::error!("Failed to read texture `{}`: {}", file.display(), e);
+                        return None;
+                    }
+                };
+                // Animated textures are a vertical strip of square frames.
+                if texture.width() == 0 || texture.height() % texture.width() != 0 {
+                    tracing::warn!(
+                        "Ignoring texture `{}` with invalid size: {}x{} (expected a square or a strip of square frames).",
+                        file.display(),
+                        texture.width(),
+                        texture.height(),
+                    );
+                    return None;
+                }
+                Some((file, name, texture))
+            })
+            .collect::<Vec<_>>();
+
+        let Some(tile_size) = textures.iter().map(|(_, _, texture)| texture.width()).max() else {
+            return Err(AtlasError::NoTextures(
+                roots.iter().map(|root| root.as_ref().to_owned()).collect(),
+            ));
+        };
+
+        let mut id = MISSING_TEXTURE_ID + 1;
+        let mut texture_map = HashMap::new();
+        let mut animations = HashMap::new();
+        let mut tiles = vec![missing_texture(tile_size)];
+        let mut normal_tiles = vec![default_map(TextureMap::Normal, tile_size)];
+        let mut specular_tiles = vec![default_map(TextureMap::Specular, tile_size)];
+        for (file, name, texture) in &textures {
+            let frame_size = texture.width();
+            let frames = texture.height() / frame_size;
+
+            tracing::info!(?id, ?frames, file = ?file.display(), "Loading texture");
+            if frame_size != tile_size {
+                tracing::info!(
+                    file = ?file.display(),
+                    "Scaling texture from {}x{} to {}x{}",
+                    frame_size,
+                    frame_size,
+                    tile_size,
+                    tile_size
+                );
+            }
+            for frame in 0..frames {
+                let frame = texture
+                    .view(0, frame * frame_size, frame_size, frame_size)
+                    .to_image();
+                tiles.push(scale(frame, tile_size));
+            }
+            normal_tiles.extend(load_map(
+                &files,
+                name,
+                TextureMap::Normal,
+                frames,
+                tile_size,
+            ));
+            specular_tiles.extend(load_map(
+                &files,
+                name,
+                TextureMap::Specular,
+                frames,
+                tile_size,
+            ));
+            if frames > 1 {
+                let animation = Animation::load(file.with_extension("toml"), frames);
+                tracing::info!(?animation, file = ?file.display(), "Animated texture");
+                animations.insert(id, animation);
+            }
+            texture_map.insert((*name).clone(), id);
+            id += frames;
+        }
+
         // the number of tiles per row/column
-        let tile_count = ((files.len() + 1) as f32).sqrt().ceil() as u32;
+        let tile_count = (tiles.len() as f32).sqrt().ceil() as u32;
 
-        let first_image = image::open(&files[0]).unwrap();
-        let atlas_width = first_image.width() * tile_count;
-        let atlas_height = first_image.height() * tile_count;
-        let mut buffer = RgbaImage::new(atlas_width, atlas_height);
+        let padding = tile_size / 2;
+        // Every tile takes twice its size, so tiles stay aligned to
+        // texel boundaries down to the mipmap level where they are 1x1.
+        let cell_size = tile_size + 2 * padding;
+        let atlas_width = cell_size * tile_count;
+        let atlas_height = cell_size * tile_count;
 
-        // TODO: write default texture
+        let mip_levels = if tile_size.is_power_of_two() {
+            tile_size.ilog2() + 1
+        } else {
+            tracing::warn!(
+                "Texture size {} is not a power of two, mipmaps are disabled",
+                tile_size
+            );
+            1
+        };
 
         tracing::info!(
             ?tile_count,
             ?atlas_width,
             ?atlas_height,
-            texture_width = first_image.width(),
-            texture_height = first_image.height()
+            texture_width = tile_size,
+            texture_height = tile_size
         );
 
-        let mut id = 1u32;
-        let mut texture_map = HashMap::new();
-        for file in &files {
-            let texture = match image::open(file) {
-                Ok(t) => t,
-                Err(e) => {
-                    tracing::error!("Failed to read texture: {}", e);
-                    continue;
+        let mut atlas = Self {
+            buf: RgbaImage::new(0, 0),
+            size: atlas_width,
+            tile_size,
+            padding,
+            mip_levels,
+            tiles,
+            normal_tiles,
+            specular_tiles,
+            texture_map,
+            animations,
+        };
+        atlas.buf = atlas.pack(TextureMap::Albedo);
+        Ok(atlas)
+    }
+
+    /// Every tile of a map, indexed by texture id.
+    pub fn map_tiles(&self, map: TextureMap) -> &[RgbaImage] {
+        match map {
+            TextureMap::Albedo => &self.tiles,
+            TextureMap::Normal => &self.normal_tiles,
+            TextureMap::Specular => &self.specular_tiles,
+        }
+    }
+
+    /// Packs every tile of a map into a single image.
+    fn pack(&self, map: TextureMap) -> RgbaImage {
+        let tiles = self.map_tiles(map);
+        let (tile_size, padding) = (self.tile_size, self.padding);
+        let cell_size = tile_size + 2 * padding;
+        let tile_count = self.size / cell_size;
+        let mut buffer = RgbaImage::new(self.size, self.size);
+        for (id, tile) in tiles.iter().enumerate() {
+            let x = (id as u32 % tile_count) * cell_size;
+            let y = (id as u32 / tile_count) * cell_size;
+            // Copy the texture into the centre of its cell, stretching its edges over the padding.
+            for cell_y in 0..cell_size {
+                for cell_x in 0..cell_size {
+                    let tex_x = cell_x.saturating_sub(padding).min(tile_size - 1);
+                    let tex_y = cell_y.saturating_sub(padding).min(tile_size - 1);
+                    buffer.put_pixel(x + cell_x, y + cell_y, *tile.get_pixel(tex_x, tex_y));
                 }
-            };
-            if texture.width() != first_image.width() || texture.height() != first_image.height() {
-                tracing::warn!(
-                    "Ignoring texture with invalid size: {}x{} (expected {}x{}).",
-                    texture.width(),
-                    texture.height(),
-                    first_image.width(),
-                    first_image.height(),
-                );
-                continue;
             }
+        }
+        buffer
+    }
 
-            tracing::info!(?id, file = ?file.display(), "Packing texture");
+    /// Creates every mipmap level of the atlas of a map, starting with the full size one.
+    pub fn mipmaps(&self, map: TextureMap) -> Vec<RgbaImage> {
+        let atlas = match map {
+            TextureMap::Albedo => self.buf.clone(),
+            _ => self.pack(map),
+        };
+        self.mip_chain(atlas, map)
+    }
 
-            let x = (id % tile_count) * first_image.width();
-            let y = (id / tile_count) * first_image.height();
-            // TODO: check errors
-            let _ = buffer.copy_from(&texture, x, y);
-            // this is ugly
-            texture_map.insert(file.file_stem().unwrap().to_str().unwrap().to_owned(), id);
-            id += 1;
-        }
+    /// Creates every mipmap level of every tile of a map, to be used as the layers of a texture array.
+    pub fn layer_mipmaps(&self, map: TextureMap) -> Vec<Vec<RgbaImage>> {
+        self.map_tiles(map)
+            .iter()
+            .map(|tile| self.mip_chain(tile.clone(), map))
+            .collect()
+    }
 
-        buffer.save("atlas.png").expect("Failed to save atlas");
+    fn mip_chain(&self, image: RgbaImage, map: TextureMap) -> Vec<RgbaImage> {
+        let mut levels = vec![image];
+        for _ in 1..self.mip_levels {
+            let next = downsample(
+                levels.last().expect("there is always a level"),
+                map.is_srgb(),
+            );
+            levels.push(next);
+        }
+        levels
+    }
 
-        Self {
-            buf: buffer,
-            size: atlas_width,
-            tile_size: first_image.width(),
-            texture_map,
+    /// The packed animation of every texture that can be animated, indexed by texture id.
+    pub fn packed_animations(&self) -> Vec<u32> {
+        let mut packed = vec![0; MAX_ANIMATED_TEXTURES];
+        for (&id, animation) in &self.animations {
+            match packed.get_mut(id as usize) {
+                Some(slot) => *slot = animation.pack(),
+                None => tracing::warn!(
+                    "The texture {} has too many textures before it to be animated",
+                    id
+                ),
+            }
         }
+        packed
     }
 
     pub fn get_texture_id(&self, texture_name: &str) -> Option<u32> {
         self.texture_map.get(texture_name).copied()
     }
+
+    /// The id of a texture, or [`MISSING_TEXTURE_ID`] if there is no texture with this name.
+    pub fn texture_id(&self, texture_name: &str) -> u32 {
+        self.get_texture_id(texture_name)
+            .unwrap_or(MISSING_TEXTURE_ID)
+    }
+}
+
+/// Scales a texture to `size`x`size`.
+///
+/// Texels are repeated or skipped rather than blended, which
+/// keeps low resolution textures sharp when they are enlarged.
+fn scale(texture: RgbaImage, size: u32) -> RgbaImage {
+    if texture.width() == size {
+        return texture;
+    }
+    image::imageops::resize(&texture, size, size, FilterType::Nearest)
+}
+
+/// A tile filled with the default value of a map.
+fn default_map(map: TextureMap, size: u32) -> RgbaImage {
+    RgbaImage::from_pixel(size, size, map.default_texel())
+}
+
+/// Loads the `frames` tiles of a map of the texture `name`.
+///
+/// A map with a single frame is used for every frame of an animated texture.
+/// The default map is used if the texture does not have one.
+fn load_map(
+    files: &BTreeMap<String, PathBuf>,
+    name: &str,
+    map: TextureMap,
+    frames: u32,
+    tile_size: u32,
+) -> Vec<RgbaImage> {
+    let default = || vec![default_map(map, tile_size); frames as usize];
+    let Some(file) = files.get(&format!("{}{}", name, map.suffix())) else {
+        return default();
+    };
+    let image = match image::open(file) {
+        Ok(image) => image,
+        Err(e) => {
+            tracing::error!("Failed to read texture `{}`: {}", file.display(), e);
+            return default();
+        }
+    };
+    let frame_size = image.width();
+    let map_frames = image.height().checked_div(frame_size).unwrap_or(0);
+    if image.height() % frame_size.max(1) != 0 || (map_frames != 1 && map_frames != frames) {
+        tracing::warn!(
+            "Ignoring `{}` with invalid size: {}x{} (expected a square or a strip of {} square frames).",
+            file.display(),
+            image.width(),
+            image.height(),
+            frames,
+        );
+        return default();
+    }
+    tracing::info!(file = ?file.display(), ?map, "Loading texture map");
+    (0..frames)
+        .map(|frame| {
+            let frame = frame % map_frames;
+            let tile = image
+                .view(0, frame * frame_size, frame_size, frame_size)
+                .to_image();
+            scale(tile, tile_size)
+        })
+        .collect()
+}
+
+/// A magenta and black checkerboard, which stands out from every other texture.
+fn missing_texture(size: u32) -> RgbaImage {
+    let half = (size / 2).max(1);
+    RgbaImage::from_fn(size, size, |x, y| {
+        if (x / half + y / half).is_multiple_of(2) {
+            Rgba([255, 0, 255, 255])
+        } else {
+            Rgba([0, 0, 0, 255])
+        }
+    })
+}
+
+/// Halves the size of an image, averaging every 2x2 block of texels.
+///
+/// The colors of sRGB images are averaged in linear space.
+fn downsample(image: &RgbaImage, srgb: bool) -> RgbaImage {
+    let gamma = if srgb { 2.2 } else { 1.0 };
+    let to_linear = |c: u8| (c as f32 / 255.0).powf(gamma);
+    let to_srgb = |c: f32| (c.powf(1.0 / gamma) * 255.0).round() as u8;
+
+    let (width, height) = ((image.width() / 2).max(1), (image.height() / 2).max(1));
+    RgbaImage::from_fn(width, height, |x, y| {
+        let mut color = [0.0; 3];
+        let mut alpha = 0.0;
+        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let texel = image.get_pixel(
+                (x * 2 + dx).min(image.width() - 1),
+                (y * 2 + dy).min(image.height() - 1),
+            );
+            for (sum, c) in color.iter_mut().zip(texel.0) {
+                *sum += to_linear(c) / 4.0;
+            }
+            alpha += texel.0[3] as f32 / 4.0;
+        }
+        Rgba([
+            to_srgb(color[0]),
+            to_srgb(color[1]),
+            to_srgb(color[2]),
+            alpha.round() as u8,
+        ])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use image::{Rgba, RgbaImage};
+
+    use super::{downsample, Animation, AtlasError, BlockAtlas, TextureMap, MISSING_TEXTURE_ID};
+
+    /// A directory for the files of a test, removed when dropped, even if the test panics.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("explora-{}-{}", name, std::process::id()));
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+
+        fn path(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Writes a `width`x`height` texture of a single color to `<dir>/<name>.png`.
+    fn write_png(dir: &Path, name: &str, width: u32, height: u32, color: Rgba<u8>) {
+        std::fs::create_dir_all(dir).unwrap();
+        RgbaImage::from_pixel(width, height, color)
+            .save(dir.join(format!("{name}.png")))
+            .unwrap();
+    }
+
+    #[test]
+    fn downsample_test() {
+        let mut image = RgbaImage::from_pixel(4, 2, Rgba([255, 255, 255, 255]));
+        image.put_pixel(0, 0, Rgba([0, 0, 0, 255]));
+        image.put_pixel(1, 1, Rgba([0, 0, 0, 255]));
+
+        let half = downsample(&image, true);
+        assert_eq!(half.dimensions(), (2, 1));
+        assert_eq!(half.get_pixel(1, 0), &Rgba([255, 255, 255, 255]));
+        // Averaging in linear space keeps half black, half white blocks bright.
+        assert!(half.get_pixel(0, 0).0[0] > 180);
+
+        let last = downsample(&downsample(&half, true), true);
+        assert_eq!(last.dimensions(), (1, 1));
+    }
+
+    #[test]
+    fn pack_animation_test() {
+        let animation = Animation {
+            frame_time: 0.25,
+            interpolate: true,
+            frames: 4,
+        };
+        let packed = animation.pack();
+        assert_eq!(packed & 0xff, 4);
+        assert_eq!(packed >> 8 & 1, 1);
+        assert_eq!(packed >> 9, 250);
+    }
+
+    #[test]
+    fn load_textures_test() {
+        let dir = TempDir::new("atlas-test");
+        assert!(matches!(
+            BlockAtlas::new(&[dir.path()]),
+            Err(AtlasError::NoTextures(_))
+        ));
+
+        for (name, color) in [("stone", 1), ("dirt", 2), ("grass", 3)] {
+            write_png(dir.path(), name, 4, 4, Rgba([color, 0, 0, 255]));
+        }
+        let atlas = BlockAtlas::new(&[dir.path()]).unwrap();
+
+        // Ids follow the alphabetical order of the names.
+        assert_eq!(atlas.get_texture_id("dirt"), Some(1));
+        assert_eq!(atlas.get_texture_id("grass"), Some(2));
+        assert_eq!(atlas.get_texture_id("stone"), Some(3));
+        assert_eq!(atlas.tiles[3].get_pixel(0, 0), &Rgba([1, 0, 0, 255]));
+        assert_eq!(atlas.texture_id("unknown"), MISSING_TEXTURE_ID);
+        let missing = &atlas.tiles[MISSING_TEXTURE_ID as usize];
+        assert_ne!(missing.get_pixel(0, 0), missing.get_pixel(3, 0));
+
+        let path = dir.path().to_owned();
+        drop(dir);
+        assert!(matches!(
+            BlockAtlas::new(&[&path]),
+            Err(AtlasError::ReadDir { .. })
+        ));
+    }
+
+    #[test]
+    fn texture_pack_test() {
+        let dir = TempDir::new("pack-test");
+        let (base, pack) = (dir.path().join("base"), dir.path().join("pack"));
+        write_png(&base, "dirt", 2, 2, Rgba([1, 0, 0, 255]));
+        write_png(&base, "stone", 2, 2, Rgba([2, 0, 0, 255]));
+        std::fs::create_dir_all(&pack).unwrap();
+        let mut stone = RgbaImage::from_pixel(8, 8, Rgba([3, 0, 0, 255]));
+        stone.put_pixel(7, 7, Rgba([4, 0, 0, 255]));
+        stone.save(pack.join("stone.png")).unwrap();
+        // The maps of the base stone don't match the pack's stone, unlike the maps of
+        // the dirt, which isn't overridden, and the maps added by the pack.
+        write_png(&base, "stone_n", 2, 2, Rgba([1, 2, 3, 255]));
+        write_png(&base, "stone_s", 2, 2, Rgba([4, 5, 6, 255]));
+        write_png(&base, "dirt_n", 2, 2, Rgba([7, 8, 9, 255]));
+        write_png(&pack, "dirt_s", 2, 2, Rgba([10, 11, 12, 255]));
+
+        let atlas = BlockAtlas::new(&[&base, &pack]).unwrap();
+
+        // Every texture is scaled to the largest resolution.
+        assert_eq!(atlas.tile_size, 8);
+        let dirt = &atlas.tiles[atlas.texture_id("dirt") as usize];
+        assert_eq!(dirt.dimensions(), (8, 8));
+        assert!(dirt.pixels().all(|p| p == &Rgba([1, 0, 0, 255])));
+        // The pack replaces the base texture.
+        let stone = atlas.texture_id("stone") as usize;
+        assert_eq!(atlas.tiles[stone].get_pixel(0, 0), &Rgba([3, 0, 0, 255]));
+        assert_eq!(atlas.tiles[stone].get_pixel(7, 7), &Rgba([4, 0, 0, 255]));
+        assert_eq!(
+            atlas.normal_tiles[stone].get_pixel(0, 0),
+            &TextureMap::Normal.default_texel()
+        );
+        assert_eq!(
+            atlas.specular_tiles[stone].get_pixel(0, 0),
+            &TextureMap::Specular.default_texel()
+        );
+        let dirt = atlas.texture_id("dirt") as usize;
+        assert_eq!(
+            atlas.normal_tiles[dirt].get_pixel(0, 0),
+            &Rgba([7, 8, 9, 255])
+        );
+        assert_eq!(
+            atlas.specular_tiles[dirt].get_pixel(0, 0),
+            &Rgba([10, 11, 12, 255])
+        );
+    }
+
+    #[test]
+    fn texture_maps_test() {
+        let dir = TempDir::new("maps-test");
+        write_png(dir.path(), "dirt", 2, 2, Rgba([1, 0, 0, 255]));
+        // A two frame animation, with a single frame specular map.
+        write_png(dir.path(), "lava", 2, 4, Rgba([2, 0, 0, 255]));
+        write_png(dir.path(), "dirt_n", 2, 2, Rgba([0, 0, 255, 255]));
+        write_png(dir.path(), "lava_s", 2, 2, Rgba([200, 50, 0, 255]));
+
+        let atlas = BlockAtlas::new(&[dir.path()]).unwrap();
+
+        // Maps are not textures on their own.
+        assert_eq!(atlas.get_texture_id("dirt_n"), None);
+        assert_eq!(atlas.tiles.len(), 4);
+        assert_eq!(atlas.normal_tiles.len(), 4);
+        assert_eq!(atlas.specular_tiles.len(), 4);
+
+        let dirt = atlas.texture_id("dirt") as usize;
+        let lava = atlas.texture_id("lava") as usize;
+        assert_eq!(
+            atlas.normal_tiles[dirt].get_pixel(0, 0),
+            &Rgba([0, 0, 255, 255])
+        );
+        assert_eq!(
+            atlas.normal_tiles[lava].get_pixel(0, 0),
+            &TextureMap::Normal.default_texel()
+        );
+        assert_eq!(
+            atlas.specular_tiles[dirt].get_pixel(0, 0),
+            &TextureMap::Specular.default_texel()
+        );
+        for frame in [lava, lava + 1] {
+            assert_eq!(
+                atlas.specular_tiles[frame].get_pixel(1, 1),
+                &Rgba([200, 50, 0, 255])
+            );
+        }
+    }
 }
@@ -63,7 +63,7 @@ impl<T: Copy + bytemuck::Pod> Buffer<T> {
         self.buf.slice(..)
     }
 
-    pub fn as_entire_binding(&self) -> wgpu::BindingResource {
+    pub fn as_entire_binding(&self) -> wgpu::BindingResource<'_> {
         self.buf.as_entire_binding()
     }
 
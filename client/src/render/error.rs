@@ -0,0 +1,90 @@
+use std::fmt;
+
+use super::atlas::AtlasError;
+
+/// Errors that can happen while setting up or using the [`Renderer`](super::Renderer).
+#[derive(Debug)]
+pub enum RenderError {
+    /// The window does not provide the handles needed to draw on it.
+    WindowHandle(wgpu::rwh::HandleError),
+    /// The window surface could not be created.
+    CreateSurface(wgpu::CreateSurfaceError),
+    /// No graphics adapter is available, or none of them can draw on the window.
+    NoAdapter,
+    /// The adapter can not draw on the window surface.
+    UnsupportedSurface(wgpu::AdapterInfo),
+    /// The adapter does not support the features or limits the renderer needs.
+    RequestDevice(wgpu::RequestDeviceError),
+    /// The window surface could not provide a frame to draw on.
+    Surface(wgpu::SurfaceError),
+    /// The block textures could not be loaded.
+    BlockTextures(AtlasError),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::WindowHandle(e) => write!(f, "Could not get the window handle: {}", e),
+            RenderError::CreateSurface(e) => write!(f, "Could not create the window surface: {}", e),
+            RenderError::NoAdapter => write!(
+                f,
+                "No graphics adapter found. Make sure your drivers support Vulkan, Metal, DirectX 12 or OpenGL ES 3"
+            ),
+            RenderError::UnsupportedSurface(info) => write!(
+                f,
+                "The graphics adapter `{}` ({:?}) can not draw on this window",
+                info.name, info.backend
+            ),
+            RenderError::RequestDevice(e) => write!(
+                f,
+                "The graphics adapter does not support the required features: {}",
+                e
+            ),
+            RenderError::Surface(e) => write!(f, "Could not get a frame to draw on: {}", e),
+            RenderError::BlockTextures(e) => write!(f, "Could not load the block textures: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RenderError::WindowHandle(e) => Some(e),
+            RenderError::CreateSurface(e) => Some(e),
+            RenderError::RequestDevice(e) => Some(e),
+            RenderError::Surface(e) => Some(e),
+            RenderError::BlockTextures(e) => Some(e),
+            RenderError::NoAdapter | RenderError::UnsupportedSurface(_) => None,
+        }
+    }
+}
+
+impl From<wgpu::rwh::HandleError> for RenderError {
+    fn from(e: wgpu::rwh::HandleError) -> Self {
+        RenderError::WindowHandle(e)
+    }
+}
+
+impl From<wgpu::CreateSurfaceError> for RenderError {
+    fn from(e: wgpu::CreateSurfaceError) -> Self {
+        RenderError::CreateSurface(e)
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for RenderError {
+    fn from(e: wgpu::RequestDeviceError) -> Self {
+        RenderError::RequestDevice(e)
+    }
+}
+
+impl From<wgpu::SurfaceError> for RenderError {
+    fn from(e: wgpu::SurfaceError) -> Self {
+        RenderError::Surface(e)
+    }
+}
+
+impl From<AtlasError> for RenderError {
+    fn from(e: AtlasError) -> Self {
+        RenderError::BlockTextures(e)
+    }
+}
@@ -1,21 +1,54 @@
-use common::chunk::Chunk;
+use common::{block::BlockId, chunk::Chunk, face::Face, terrain::Terrain};
 use vek::{Vec2, Vec3};
 
 use crate::block::BlockMap;
 
 use super::{atlas::BlockAtlas, vertex::TerrainVertex};
 
+/// The corners of each face, in the order expected by the terrain shader
+/// (top left, bottom left, bottom right, top right when looking at the face).
+fn face_corners(face: Face) -> [Vec3<f32>; 4] {
+    let (x, y, z) = (Vec3::unit_x(), Vec3::unit_y(), Vec3::unit_z());
+    let zero = Vec3::zero();
+    match face {
+        Face::North => [x + y + z, x + z, z, y + z],
+        Face::South => [y, zero, x, x + y],
+        Face::East => [x + y, x, x + z, x + y + z],
+        Face::West => [z + y, z, zero, y],
+        Face::Top => [z + y, y, y + x, y + x + z],
+        Face::Bottom => [zero, z, z + x, x],
+    }
+}
+
 pub fn create_chunk_mesh(
-    c: &Chunk,
+    terrain: &Terrain,
+    chunk_pos: Vec2<i32>,
     block_atlas: &BlockAtlas,
     block_map: &BlockMap,
 ) -> Vec<TerrainVertex> {
     let mut mesh = vec![];
+    let Some(c) = terrain.chunk(chunk_pos) else {
+        return mesh;
+    };
+
+    // Looks up neighbours in this chunk first, only going through the terrain
+    // for blocks that belong to other chunks.
+    let block_at = |pos: Vec3<i32>| -> Option<BlockId> {
+        if pos.x < 0 || pos.z < 0 || pos.x >= Chunk::SIZE.x as i32 || pos.z >= Chunk::SIZE.z as i32
+        {
+            terrain.get(Terrain::world_pos(chunk_pos, pos))
+        } else {
+            c.get(pos)
+        }
+    };
 
     for pos in c.iter_pos() {
         let block = c
             .get(pos)
             .expect("there is always a block for a local block pos");
+        if block.is_air() {
+            continue;
+        }
 
         let block_settings = block_map
             .blocks
@@ -24,115 +57,28 @@ pub fn create_chunk_mesh(
 
         let offset = pos.map(|f| f as f32);
 
-        // North
-        if Chunk::out_of_bounds(pos + Vec3::unit_z()) {
-            let north_texture = block_atlas
-                .get_texture_id(&block_settings.textures.north)
-                .unwrap();
-
-            mesh.push(TerrainVertex::new(
-                Vec3::unit_x() + Vec3::unit_y() + Vec3::unit_z() + offset,
-                north_texture,
-            ));
-            mesh.push(TerrainVertex::new(
-                Vec3::unit_x() + Vec3::unit_z() + offset,
-                north_texture,
-            ));
-            mesh.push(TerrainVertex::new(
-                Vec3::zero() + Vec3::unit_z() + offset,
-                north_texture,
-            ));
-            mesh.push(TerrainVertex::new(
-                Vec3::unit_y() + Vec3::unit_z() + offset,
-                north_texture,
-            ));
-        }
-
-        // South
-        if Chunk::out_of_bounds(pos - Vec3::unit_z()) {
-            let south_texture = block_atlas
-                .get_texture_id(&block_settings.textures.south)
-                .unwrap();
-
-            mesh.push(TerrainVertex::new(Vec3::unit_y() + offset, south_texture));
-            mesh.push(TerrainVertex::new(Vec3::zero() + offset, south_texture));
-            mesh.push(TerrainVertex::new(Vec3::unit_x() + offset, south_texture));
-            mesh.push(TerrainVertex::new(
-                Vec3::unit_x() + Vec3::unit_y() + offset,
-                south_texture,
-            ));
-        }
-
-        // East
-        if Chunk::out_of_bounds(pos + Vec3::unit_x()) {
-            let east_texture = block_atlas
-                .get_texture_id(&block_settings.textures.east)
-                .unwrap();
-
-            mesh.push(TerrainVertex::new(
-                Vec3::unit_x() + Vec3::unit_y() + offset,
-                east_texture,
-            ));
-            mesh.push(TerrainVertex::new(Vec3::unit_x() + offset, east_texture));
-            mesh.push(TerrainVertex::new(
-                Vec3::unit_x() + Vec3::unit_z() + offset,
-                east_texture,
-            ));
-            mesh.push(TerrainVertex::new(
-                Vec3::unit_x() + Vec3::unit_z() + Vec3::unit_y() + offset,
-                east_texture,
-            ));
-        }
-
-        // West
-        if Chunk::out_of_bounds(pos - Vec3::unit_x()) {
-            let west_texture = block_atlas
-                .get_texture_id(&block_settings.textures.west)
-                .unwrap();
-
-            mesh.push(TerrainVertex::new(
-                Vec3::unit_z() + Vec3::unit_y() + offset,
-                west_texture,
-            ));
-            mesh.push(TerrainVertex::new(Vec3::unit_z() + offset, west_texture));
-            mesh.push(TerrainVertex::new(Vec3::zero() + offset, west_texture));
-            mesh.push(TerrainVertex::new(Vec3::unit_y() + offset, west_texture));
-        }
-
-        // Top
-        if Chunk::out_of_bounds(pos + Vec3::unit_y()) {
-            let top_texture = block_atlas
-                .get_texture_id(&block_settings.textures.top)
-                .unwrap();
-
-            mesh.push(TerrainVertex::new(
-                Vec3::unit_z() + Vec3::unit_y() + offset,
-                top_texture,
-            ));
-            mesh.push(TerrainVertex::new(Vec3::unit_y() + offset, top_texture));
-            mesh.push(TerrainVertex::new(
-                Vec3::unit_y() + Vec3::unit_x() + offset,
-                top_texture,
-            ));
-            mesh.push(TerrainVertex::new(
-                Vec3::unit_y() + Vec3::unit_x() + Vec3::unit_z() + offset,
-                top_texture,
-            ));
-        }
-
-        // Bottom
-        if Chunk::out_of_bounds(pos - Vec3::unit_y()) {
-            let bottom_texture = block_atlas
-                .get_texture_id(&block_settings.textures.west)
-                .unwrap();
-
-            mesh.push(TerrainVertex::new(Vec3::zero() + offset, bottom_texture));
-            mesh.push(TerrainVertex::new(Vec3::unit_z() + offset, bottom_texture));
-            mesh.push(TerrainVertex::new(
-                Vec3::unit_z() + Vec3::unit_x() + offset,
-                bottom_texture,
-            ));
-            mesh.push(TerrainVertex::new(Vec3::unit_x() + offset, bottom_texture));
+        for face in Face::ALL {
+            let neighbour = pos + face.normal();
+            // Anything outside of the loaded terrain is considered air.
+            if block_at(neighbour).is_some_and(BlockId::is_opaque) {
+                continue;
+            }
+
+            let texture = block_atlas.texture_id(block_settings.textures.get(face));
+            // Faces are lit by the block in front of them.
+            let neighbour = Terrain::world_pos(chunk_pos, neighbour);
+            let sky_light = terrain.sky_light(neighbour);
+            let block_light = terrain.block_light(neighbour);
+
+            for corner in face_corners(face) {
+                mesh.push(TerrainVertex::new(
+                    corner + offset,
+                    texture,
+                    face,
+                    sky_light,
+                    block_light,
+                ));
+            }
         }
     }
     mesh
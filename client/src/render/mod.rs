@@ -1,24 +1,73 @@
 pub mod atlas;
+pub mod blit;
 pub mod buffer;
+pub mod error;
 pub mod mesh;
+pub mod outline;
+pub mod shadow;
+pub mod sky;
 pub mod texture;
 pub mod vertex;
 pub mod voxels;
 
-use vek::Mat4;
+use std::path::{Path, PathBuf};
+
+use common::face::Face;
+use image::RgbaImage;
+use vek::{Mat4, Rgba, Vec3};
 use winit::window::Window;
 
-use crate::{block::BlockMap, scene::Scene};
+use crate::{
+    block::BlockMap,
+    camera::Matrices,
+    scene::Scene,
+    settings::{GraphicsSettings, PresentMode},
+};
+
+use self::{
+    atlas::{BlockAtlas, BlockTextureMode, TextureMap},
+    blit::Blit,
+    buffer::Buffer,
+    error::RenderError,
+    outline::Outline,
+    shadow::{ShadowMode, Shadows},
+    sky::{Fog, Lighting, Sky, SkyColors},
+    texture::{Texture, TextureFilter},
+    voxels::Voxels,
+};
+
+/// The time sent to the GPU wraps around after this many seconds, so that it stays precise.
+/// Animations jump when it does.
+const ANIMATION_PERIOD: f64 = 3600.0;
+
+/// The base game assets used by windowed renderers, relative to the working directory.
+const ASSETS: &str = "assets";
+/// The block textures, relative to the assets or a resource pack.
+const BLOCK_TEXTURES: &str = "textures/blocks";
 
-use self::{atlas::BlockAtlas, buffer::Buffer, texture::Texture, voxels::Voxels};
+/// Set this environment variable to a file path to save the block atlas there, for debugging.
+const DUMP_ATLAS: &str = "EXPLORA_DUMP_ATLAS";
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Uniforms {
     proj: [[f32; 4]; 4],
     view: [[f32; 4]; 4],
+    inv_proj_view: [[f32; 4]; 4],
+    cam_pos: [f32; 4],
+    sky_zenith: [f32; 4],
+    sky_horizon: [f32; 4],
+    sun_dir: [f32; 4],
+    light_dir: [f32; 4],
+    light_color: [f32; 4],
+    ambient: [f32; 4],
     atlas_size: u32,
     tile_size: u32,
+    fog_start: f32,
+    fog_end: f32,
+    tile_padding: u32,
+    /// Seconds used to animate textures, wrapping around every [`ANIMATION_PERIOD`] seconds.
+    time: f32,
     _padding: [u32; 2],
 }
 impl Default for Uniforms {
@@ -26,60 +75,139 @@ impl Default for Uniforms {
         Self {
             proj: Mat4::identity().into_col_arrays(),
             view: Mat4::identity().into_col_arrays(),
+            inv_proj_view: Mat4::identity().into_col_arrays(),
+            cam_pos: [0.0; 4],
+            sky_zenith: [0.0; 4],
+            sky_horizon: [0.0; 4],
+            sun_dir: [0.0; 4],
+            light_dir: [0.0; 4],
+            light_color: [0.0; 4],
+            ambient: [0.0; 4],
             atlas_size: 0,
             tile_size: 0,
-            _padding: [0, 0],
+            fog_start: 0.0,
+            fog_end: 0.0,
+            tile_padding: 0,
+            time: 0.0,
+            _padding: [0; 2],
         }
     }
 }
 
 impl Uniforms {
-    pub fn new(proj: Mat4<f32>, view: Mat4<f32>, atlas_size: u32, tile_size: u32) -> Self {
+    pub fn new(
+        matrices: &Matrices,
+        cam_pos: Vec3<f32>,
+        sky: SkyColors,
+        lighting: Lighting,
+        fog: Fog,
+        block_atlas: &BlockAtlas,
+        time: f64,
+    ) -> Self {
         Self {
-            proj: proj.into_col_arrays(),
-            view: view.into_col_arrays(),
-            atlas_size,
-            tile_size,
-            _padding: [0, 0],
+            proj: matrices.proj.into_col_arrays(),
+            view: matrices.view.into_col_arrays(),
+            inv_proj_view: (matrices.proj * matrices.view).inverted().into_col_arrays(),
+            cam_pos: cam_pos.with_w(1.0).into_array(),
+            sky_zenith: Rgba::from_opaque(sky.zenith).into_array(),
+            sky_horizon: Rgba::from_opaque(sky.horizon).into_array(),
+            sun_dir: lighting.sun_dir.with_w(0.0).into_array(),
+            light_dir: lighting.light_dir.with_w(0.0).into_array(),
+            light_color: Rgba::from_opaque(lighting.light_color).into_array(),
+            ambient: Rgba::from_opaque(lighting.ambient).into_array(),
+            atlas_size: block_atlas.size,
+            tile_size: block_atlas.tile_size,
+            fog_start: fog.start,
+            fog_end: fog.end,
+            tile_padding: block_atlas.padding,
+            time: time.rem_euclid(ANIMATION_PERIOD) as f32,
+            _padding: [0; 2],
         }
     }
 }
 
+/// Where the renderer draws its frames.
+enum RenderTarget {
+    /// A window surface, presented after every frame.
+    Surface(wgpu::Surface<'static>),
+    /// A texture that can be read back, used to render without a window.
+    Offscreen(wgpu::Texture),
+}
+
+/// Textures the scene is drawn into, besides the render target itself.
+struct FrameTargets {
+    depth: Texture,
+    /// Multisampled colour texture, resolved at the end of the main pass.
+    msaa: Option<Texture>,
+    /// The frame at the render resolution, when it differs from the size of the target.
+    scaled: Option<(Texture, wgpu::BindGroup)>,
+}
+
 /// Manages the rendering of the application.
 pub struct Renderer {
-    /// Surface on which the renderer will draw.
-    surface: wgpu::Surface<'static>,
+    /// Target on which the renderer will draw.
+    target: RenderTarget,
+    /// The physical device, used to query what the hardware supports.
+    adapter: wgpu::Adapter,
     /// The Logical Device, used for interacting with the GPU.
     device: wgpu::Device,
     /// A Queue handle. Used for command submission.
     queue: wgpu::Queue,
     /// The surface configuration details.
+    ///
+    /// When rendering offscreen it describes the offscreen texture.
     config: wgpu::SurfaceConfiguration,
     /// Globals sent to the GPU.
     uniforms_buffer: Buffer<Uniforms>,
+    /// Layout of the common uniforms, needed to recreate pipelines
+    common_bg_layout: wgpu::BindGroupLayout,
     /// Represents the bidings for common uniforms
     common_bg: wgpu::BindGroup,
+    /// Mipmapped block textures, bound in the common bind group
+    block_texture: Texture,
+    /// Normal maps of the block textures
+    block_normals: Texture,
+    /// Specular maps of the block textures
+    block_speculars: Texture,
+    /// How every block texture is animated
+    animations_buffer: Buffer<[u32; 4]>,
+    /// How block textures are stored, only chosen when the renderer is created
+    block_textures: BlockTextureMode,
+    /// Filtering used to sample the block textures
+    texture_filter: TextureFilter,
     /// A voxel renderer
     voxels: Voxels,
+    /// Sky gradient drawn behind the terrain
+    sky: Sky,
+    /// Edges of the block the player is looking at, and of the player in third person
+    outline: Outline,
+    /// Sun shadow maps
+    shadows: Shadows,
     /// Texture Atlas for blocks
     block_atlas: BlockAtlas,
-    /// Depth texture
-    depth_texture: Texture,
+    /// Settings of every block, used for meshing
+    block_map: BlockMap,
+    /// Scales frames rendered at a different resolution to the target size
+    blit: Blit,
+    /// Depth, multisampled and scaled textures
+    targets: FrameTargets,
+    /// The number of samples per pixel used for anti-aliasing
+    sample_count: u32,
+    /// The render resolution relative to the size of the target
+    render_scale: f32,
 }
 
 impl Renderer {
-    pub async fn new(window: &Window) -> Self {
+    pub async fn new(window: &Window, settings: &GraphicsSettings) -> Result<Self, RenderError> {
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
             backends: wgpu::Backends::all(),
             ..Default::default()
         });
 
+        // SAFETY: The window outlives the renderer, as both are owned by the same `Window`
+        // and the renderer is declared first so it is dropped first.
         let surface = unsafe {
-            instance
-                .create_surface_unsafe(
-                    wgpu::SurfaceTargetUnsafe::from_window(window).unwrap_unchecked(),
-                )
-                .unwrap()
+            instance.create_surface_unsafe(wgpu::SurfaceTargetUnsafe::from_window(window)?)?
         };
 
         let adapter = instance
@@ -89,41 +217,189 @@ impl Renderer {
                 force_fallback_adapter: false,
             })
             .await
-            .unwrap();
+            .ok_or(RenderError::NoAdapter)?;
+        tracing::info!(adapter = ?adapter.get_info(), "Selected graphics adapter");
+
+        // WebGL doesn't support all of wgpu's features, so if
+        // we're building for the web, we'll have to disable some.
+        let limits = if cfg!(target_arch = "wasm32") {
+            wgpu::Limits::downlevel_webgl2_defaults()
+        } else {
+            wgpu::Limits::default()
+        };
+        let (device, queue) = Self::request_device(&adapter, limits).await?;
+        let size = window.inner_size();
+
+        let mut config = surface
+            .get_default_config(&adapter, size.width.max(1), size.height.max(1))
+            .ok_or_else(|| RenderError::UnsupportedSurface(adapter.get_info()))?;
+        config.present_mode =
+            Self::supported_present_mode(&surface, &adapter, settings.present_mode);
+
+        surface.configure(&device, &config);
+
+        Self::with_target(
+            adapter,
+            device,
+            queue,
+            config,
+            RenderTarget::Surface(surface),
+            settings,
+            Path::new(ASSETS),
+        )
+    }
+
+    /// Creates a renderer that draws into an offscreen texture instead of a window.
+    ///
+    /// Any adapter is accepted, including software ones, so this works on machines
+    /// without a GPU or a display. Frames can be read back with [`Renderer::capture_frame`].
+    ///
+    /// The base game assets are loaded from the `assets` directory.
+    pub async fn headless(
+        width: u32,
+        height: u32,
+        settings: &GraphicsSettings,
+        assets: &Path,
+    ) -> Result<Self, RenderError> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
 
-        let (device, queue) = adapter
+        let mut adapter = None;
+        for force_fallback_adapter in [false, true] {
+            adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::default(),
+                    compatible_surface: None,
+                    force_fallback_adapter,
+                })
+                .await;
+            if adapter.is_some() {
+                break;
+            }
+        }
+        let adapter = adapter.ok_or(RenderError::NoAdapter)?;
+        tracing::info!(adapter = ?adapter.get_info(), "Rendering offscreen");
+
+        // Software adapters don't reach the default limits.
+        let limits = wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits());
+        let (device, queue) = Self::request_device(&adapter, limits).await?;
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+        };
+        let texture = Self::create_offscreen_texture(&device, &config);
+
+        Self::with_target(
+            adapter,
+            device,
+            queue,
+            config,
+            RenderTarget::Offscreen(texture),
+            settings,
+            assets,
+        )
+    }
+
+    async fn request_device(
+        adapter: &wgpu::Adapter,
+        limits: wgpu::Limits,
+    ) -> Result<(wgpu::Device, wgpu::Queue), wgpu::RequestDeviceError> {
+        adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
-                    required_features: wgpu::Features::empty(),
-                    // WebGL doesn't support all of wgpu's features, so if
-                    // we're building for the web, we'll have to disable some.
-                    required_limits: if cfg!(target_arch = "wasm32") {
-                        wgpu::Limits::downlevel_webgl2_defaults()
-                    } else {
-                        wgpu::Limits::default()
-                    },
+                    // Allows every MSAA sample count the adapter supports, not only 4.
+                    required_features: adapter.features()
+                        & wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
+                    required_limits: limits,
                     label: None,
                 },
                 None, // Trace path
             )
             .await
-            .unwrap();
-        let size = window.inner_size();
-
-        let config = surface
-            .get_default_config(&adapter, size.width, size.height)
-            .unwrap();
+    }
 
-        surface.configure(&device, &config);
+    fn create_offscreen_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Texture"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
 
+    fn with_target(
+        adapter: wgpu::Adapter,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        config: wgpu::SurfaceConfiguration,
+        target: RenderTarget,
+        settings: &GraphicsSettings,
+        assets: &Path,
+    ) -> Result<Self, RenderError> {
         let uniforms_buffer = Buffer::new(
             &device,
             wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             &[Uniforms::default()],
         );
-        let block_atlas = BlockAtlas::new("assets/textures/blocks");
-        let atlas_texture = Texture::new(&device, &queue, &block_atlas.buf);
-        let block_map = BlockMap::load("assets/blocks");
+        let block_atlas = BlockAtlas::new(&Self::block_texture_roots(settings, assets))?;
+        if let Some(path) = std::env::var_os(DUMP_ATLAS) {
+            match block_atlas.buf.save(&path) {
+                Ok(()) => tracing::info!("Saved the block atlas to {:?}", path),
+                Err(e) => tracing::error!("Could not save the block atlas to {:?}: {}", path, e),
+            }
+        }
+        let block_textures =
+            Self::supported_block_texture_mode(&device, &block_atlas, settings.block_textures);
+        let [block_texture, block_normals, block_speculars] =
+            [TextureMap::Albedo, TextureMap::Normal, TextureMap::Specular].map(|map| {
+                Self::create_block_texture(
+                    &device,
+                    &queue,
+                    &block_atlas,
+                    block_textures,
+                    map,
+                    settings.texture_filter,
+                )
+            });
+        let block_map = BlockMap::load(assets.join("blocks"));
+        for block in block_map.blocks.values() {
+            for face in Face::ALL {
+                let texture = block.textures.get(face);
+                if block_atlas.get_texture_id(texture).is_none() {
+                    tracing::warn!(
+                        "The block `{}` uses the missing texture `{}`",
+                        block.name,
+                        texture
+                    );
+                }
+            }
+        }
+        // Four textures per element, uniform arrays have a stride of 16 bytes.
+        let animations = block_atlas
+            .packed_animations()
+            .chunks_exact(4)
+            .map(|chunk| [chunk[0], chunk[1], chunk[2], chunk[3]])
+            .collect::<Vec<_>>();
+        let animations_buffer = Buffer::new(&device, wgpu::BufferUsages::UNIFORM, &animations);
 
         let common_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -131,7 +407,7 @@ impl Renderer {
                 entries: &[
                     wgpu::BindGroupLayoutEntry {
                         binding: 0,
-                        visibility: wgpu::ShaderStages::VERTEX,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
                         ty: wgpu::BindingType::Buffer {
                             ty: wgpu::BufferBindingType::Uniform,
                             has_dynamic_offset: false,
@@ -144,7 +420,7 @@ impl Renderer {
                         visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Texture {
                             multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
+                            view_dimension: block_textures.view_dimension(),
                             sample_type: wgpu::TextureSampleType::Float { filterable: true },
                         },
                         count: None,
@@ -155,11 +431,144 @@ impl Renderer {
                         ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                         count: None,
                     },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // Normal and specular maps, sampled with the block texture sampler.
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: block_textures.view_dimension(),
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: block_textures.view_dimension(),
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
                 ],
             });
-        let common_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        let common_bg = Self::create_common_bg(
+            &device,
+            &common_bind_group_layout,
+            &uniforms_buffer,
+            [&block_texture, &block_normals, &block_speculars],
+            &animations_buffer,
+        );
+        let sample_count = Self::supported_sample_count(&adapter, config.format, settings.msaa);
+        let render_scale = settings.render_scale;
+        let shadows = Shadows::new(&device, settings.shadows);
+        let voxels = Voxels::new(
+            &device,
+            &common_bind_group_layout,
+            &shadows,
+            &config,
+            sample_count,
+            block_textures,
+        );
+        let sky = Sky::new(&device, &common_bind_group_layout, &config, sample_count);
+        let outline = Outline::new(&device, &common_bind_group_layout, &config, sample_count);
+        let blit = Blit::new(&device, &config);
+        let targets =
+            Self::create_frame_targets(&device, &config, &blit, sample_count, render_scale);
+        Ok(Self {
+            target,
+            adapter,
+            device,
+            queue,
+            config,
+            uniforms_buffer,
+            common_bg_layout: common_bind_group_layout,
+            common_bg,
+            block_texture,
+            block_normals,
+            block_speculars,
+            animations_buffer,
+            block_textures,
+            texture_filter: settings.texture_filter,
+            voxels,
+            sky,
+            outline,
+            shadows,
+            block_atlas,
+            block_map,
+            blit,
+            targets,
+            sample_count,
+            render_scale,
+        })
+    }
+
+    /// The directories block textures are loaded from, starting with the base game textures.
+    ///
+    /// Resource packs without block textures are skipped.
+    fn block_texture_roots(settings: &GraphicsSettings, assets: &Path) -> Vec<PathBuf> {
+        let mut roots = vec![assets.join(BLOCK_TEXTURES)];
+        for pack in &settings.resource_packs {
+            let root = pack.join(BLOCK_TEXTURES);
+            if root.is_dir() {
+                tracing::info!("Loading block textures from `{}`", root.display());
+                roots.push(root);
+            } else if !pack.is_dir() {
+                tracing::warn!("The resource pack `{}` does not exist", pack.display());
+            }
+        }
+        roots
+    }
+
+    /// Uploads one of the maps of every block texture.
+    fn create_block_texture(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        block_atlas: &BlockAtlas,
+        mode: BlockTextureMode,
+        map: TextureMap,
+        filter: TextureFilter,
+    ) -> Texture {
+        match mode {
+            BlockTextureMode::Atlas => Texture::new(
+                device,
+                queue,
+                &block_atlas.mipmaps(map),
+                map.format(),
+                filter,
+            ),
+            BlockTextureMode::Array => Texture::new_array(
+                device,
+                queue,
+                &block_atlas.layer_mipmaps(map),
+                map.format(),
+                filter,
+            ),
+        }
+    }
+
+    fn create_common_bg(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        uniforms_buffer: &Buffer<Uniforms>,
+        [block_texture, block_normals, block_speculars]: [&Texture; 3],
+        animations_buffer: &Buffer<[u32; 4]>,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Common Bind Group"),
-            layout: &common_bind_group_layout,
+            layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
@@ -167,59 +576,365 @@ impl Renderer {
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::TextureView(&atlas_texture.view),
+                    resource: wgpu::BindingResource::TextureView(&block_texture.view),
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
-                    resource: wgpu::BindingResource::Sampler(&atlas_texture.sampler),
+                    resource: wgpu::BindingResource::Sampler(&block_texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: animations_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&block_normals.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(&block_speculars.view),
                 },
             ],
+        })
+    }
+
+    /// Returns `mode`, unless the block textures don't fit in a texture array.
+    fn supported_block_texture_mode(
+        device: &wgpu::Device,
+        block_atlas: &BlockAtlas,
+        mode: BlockTextureMode,
+    ) -> BlockTextureMode {
+        let max_layers = device.limits().max_texture_array_layers;
+        if mode == BlockTextureMode::Array && block_atlas.tiles.len() as u32 > max_layers {
+            tracing::warn!(
+                "{} block textures don't fit in a texture array of {} layers, using an atlas",
+                block_atlas.tiles.len(),
+                max_layers
+            );
+            return BlockTextureMode::Atlas;
+        }
+        mode
+    }
+
+    /// Returns `mode` if the surface supports it, or vsync otherwise.
+    fn supported_present_mode(
+        surface: &wgpu::Surface,
+        adapter: &wgpu::Adapter,
+        mode: PresentMode,
+    ) -> wgpu::PresentMode {
+        let present_mode = mode.to_wgpu();
+        if surface
+            .get_capabilities(adapter)
+            .present_modes
+            .contains(&present_mode)
+        {
+            present_mode
+        } else {
+            tracing::warn!("Present mode {:?} is not supported, using vsync", mode);
+            wgpu::PresentMode::Fifo
+        }
+    }
+
+    /// Returns the highest MSAA sample count up to `requested` that the adapter supports.
+    fn supported_sample_count(
+        adapter: &wgpu::Adapter,
+        format: wgpu::TextureFormat,
+        requested: u32,
+    ) -> u32 {
+        let color = adapter.get_texture_format_features(format).flags;
+        let depth = adapter
+            .get_texture_format_features(Texture::DEPTH_FORMAT)
+            .flags;
+        let supported = |count: u32| {
+            count == 1
+                || (color.sample_count_supported(count)
+                    && color.contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE)
+                    && depth.sample_count_supported(count))
+        };
+        let sample_count = [8, 4, 2, 1]
+            .into_iter()
+            .find(|&count| count <= requested && supported(count))
+            .unwrap_or(1);
+        if sample_count != requested {
+            tracing::warn!(
+                "MSAA with {} samples is not supported, using {} instead",
+                requested,
+                sample_count
+            );
+        }
+        sample_count
+    }
+
+    /// The size the scene is rendered at, before being scaled to the size of the target.
+    fn render_size(config: &wgpu::SurfaceConfiguration, render_scale: f32) -> (u32, u32) {
+        let scale = |size: u32| ((size as f32 * render_scale).round() as u32).max(1);
+        (scale(config.width), scale(config.height))
+    }
+
+    fn create_frame_targets(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        blit: &Blit,
+        sample_count: u32,
+        render_scale: f32,
+    ) -> FrameTargets {
+        let (width, height) = Self::render_size(config, render_scale);
+        let max_size = device.limits().max_texture_dimension_2d;
+        let (width, height) = (width.min(max_size), height.min(max_size));
+
+        let msaa = (sample_count > 1)
+            .then(|| Texture::render_target(device, width, height, config.format, sample_count));
+        let scaled = (width, height) != (config.width, config.height);
+        let scaled = scaled.then(|| {
+            let texture = Texture::render_target(device, width, height, config.format, 1);
+            let bind_group = blit.bind_group(device, &texture);
+            (texture, bind_group)
         });
-        let voxels = Voxels::new(
-            &device,
-            &common_bind_group_layout,
-            &config,
-            &block_atlas,
-            &block_map,
-        );
-        let depth_texture = Texture::depth(&device, config.width, config.height);
-        Self {
-            surface,
-            device,
-            queue,
-            config,
-            uniforms_buffer,
-            common_bg,
-            voxels,
-            block_atlas,
-            depth_texture,
+        FrameTargets {
+            depth: Texture::depth(device, width, height, sample_count),
+            msaa,
+            scaled,
         }
     }
 
+    /// Applies the graphics settings that affect rendering.
+    ///
+    /// Pipelines and textures are only recreated when the settings they depend on changed.
+    pub fn apply_settings(&mut self, settings: &GraphicsSettings) {
+        if let RenderTarget::Surface(surface) = &self.target {
+            let present_mode =
+                Self::supported_present_mode(surface, &self.adapter, settings.present_mode);
+            if present_mode != self.config.present_mode {
+                self.config.present_mode = present_mode;
+                surface.configure(&self.device, &self.config);
+            }
+        }
+
+        if settings.block_textures != self.block_textures {
+            tracing::info!("Restart the game to change how block textures are stored");
+        }
+
+        if settings.texture_filter != self.texture_filter {
+            self.texture_filter = settings.texture_filter;
+            self.block_texture.sampler = Texture::sampler(&self.device, settings.texture_filter);
+            self.common_bg = Self::create_common_bg(
+                &self.device,
+                &self.common_bg_layout,
+                &self.uniforms_buffer,
+                [
+                    &self.block_texture,
+                    &self.block_normals,
+                    &self.block_speculars,
+                ],
+                &self.animations_buffer,
+            );
+        }
+
+        if settings.shadows != self.shadows.mode() {
+            self.set_shadow_mode(settings.shadows);
+        }
+
+        let sample_count =
+            Self::supported_sample_count(&self.adapter, self.config.format, settings.msaa);
+        let targets_changed =
+            sample_count != self.sample_count || settings.render_scale != self.render_scale;
+        if sample_count != self.sample_count {
+            self.sample_count = sample_count;
+            self.voxels
+                .set_sample_count(&self.device, self.config.format, sample_count);
+            self.sky = Sky::new(
+                &self.device,
+                &self.common_bg_layout,
+                &self.config,
+                sample_count,
+            );
+            self.outline = Outline::new(
+                &self.device,
+                &self.common_bg_layout,
+                &self.config,
+                sample_count,
+            );
+        }
+        self.render_scale = settings.render_scale;
+        if targets_changed {
+            self.targets = Self::create_frame_targets(
+                &self.device,
+                &self.config,
+                &self.blit,
+                self.sample_count,
+                self.render_scale,
+            );
+        }
+    }
+
+    /// Resizes the render target. Zero sized targets, e.g. minimized windows, are ignored.
     pub fn resize(&mut self, w: u32, h: u32) {
+        if w == 0 || h == 0 {
+            return;
+        }
         self.config.width = w;
         self.config.height = h;
-        self.surface.configure(&self.device, &self.config);
-        self.depth_texture = Texture::depth(&self.device, w, h);
+        match &mut self.target {
+            RenderTarget::Surface(surface) => surface.configure(&self.device, &self.config),
+            RenderTarget::Offscreen(texture) => {
+                *texture = Self::create_offscreen_texture(&self.device, &self.config)
+            }
+        }
+        self.targets = Self::create_frame_targets(
+            &self.device,
+            &self.config,
+            &self.blit,
+            self.sample_count,
+            self.render_scale,
+        );
+    }
+
+    /// Reads back the last rendered frame.
+    ///
+    /// Only available for renderers created with [`Renderer::headless`].
+    pub fn capture_frame(&self) -> Option<RgbaImage> {
+        let RenderTarget::Offscreen(texture) = &self.target else {
+            return None;
+        };
+        let (width, height) = (self.config.width, self.config.height);
+        // Rows copied into a buffer must be aligned.
+        let unpadded_bytes_per_row = 4 * width;
+        let padded_bytes_per_row = unpadded_bytes_per_row
+            .div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Frame Capture Buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Frame Capture Encoder"),
+            });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        if let Err(e) = receiver.recv().ok()? {
+            tracing::error!("Failed to read back frame: {}", e);
+            return None;
+        }
+
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+        for row in slice
+            .get_mapped_range()
+            .chunks(padded_bytes_per_row as usize)
+        {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+        RgbaImage::from_raw(width, height, pixels)
+    }
+
+    pub fn shadow_mode(&self) -> ShadowMode {
+        self.shadows.mode()
     }
 
-    pub fn render(&mut self, scene: &mut Scene) {
+    /// Changes the quality of the sun shadows.
+    pub fn set_shadow_mode(&mut self, mode: ShadowMode) {
+        self.shadows.set_mode(&self.device, mode);
+    }
+
+    /// Draws a frame of the scene.
+    ///
+    /// Frames that can't be drawn because the window surface is being resized or
+    /// is temporarily unavailable are skipped, only unrecoverable errors are returned.
+    pub fn render(&mut self, scene: &mut Scene) -> Result<(), RenderError> {
+        let dirty = scene.take_dirty_chunks();
+        if !dirty.is_empty() {
+            self.voxels.update(
+                &self.device,
+                scene.terrain(),
+                dirty,
+                &self.block_atlas,
+                &self.block_map,
+            );
+        }
+
         let matrices = scene.camera_matrices();
+        let time_of_day = scene.time_of_day();
+        let sky_colors = SkyColors::at(time_of_day);
+        let lighting = Lighting::at(time_of_day);
+        let fog = Fog::from_render_distance(scene.render_distance());
 
         self.uniforms_buffer.write(
             &self.queue,
             &[Uniforms::new(
-                matrices.proj,
-                matrices.view,
-                self.block_atlas.size,
-                self.block_atlas.tile_size,
+                &matrices,
+                scene.camera().view_pos(),
+                sky_colors,
+                lighting,
+                fog,
+                &self.block_atlas,
+                scene.time(),
             )],
         );
+        let boxes = scene
+            .target()
+            .map(|hit| Outline::block_box(hit.pos))
+            .into_iter()
+            .chain(scene.player_box())
+            .collect::<Vec<_>>();
+        self.outline.set_boxes(&self.queue, &boxes);
+        // Shadows are not needed past the point where the terrain is hidden by the fog.
+        self.shadows.update(
+            &self.queue,
+            scene.camera(),
+            matrices.view,
+            lighting.light_dir,
+            fog.end,
+        );
 
-        let output = self.surface.get_current_texture().unwrap();
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        let (output, view) = match &self.target {
+            RenderTarget::Surface(surface) => {
+                let output = match surface.get_current_texture() {
+                    Ok(output) => output,
+                    // The surface no longer matches the window, configuring it
+                    // again is enough to get frames from it on the next try.
+                    Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                        tracing::debug!("Surface lost or outdated, reconfiguring it");
+                        surface.configure(&self.device, &self.config);
+                        return Ok(());
+                    }
+                    Err(wgpu::SurfaceError::Timeout) => {
+                        tracing::warn!("Timed out waiting for a frame, skipping it");
+                        return Ok(());
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+                let view = output
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                (Some(output), view)
+            }
+            RenderTarget::Offscreen(texture) => (
+                None,
+                texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            ),
+        };
 
         let mut encoder = self
             .device
@@ -227,26 +942,43 @@ impl Renderer {
                 label: Some("Render Encoder"),
             });
 
+        for cascade in 0..self.shadows.cascade_count() {
+            let mut shadow_pass = self.shadows.begin_pass(&mut encoder, cascade);
+            self.voxels
+                .draw_shadow(&mut shadow_pass, self.shadows.cascade_bind_group(cascade));
+        }
+
+        // The scene is drawn into the scaled texture if there is one, and then
+        // stretched over the target. With MSAA it is resolved into it at the end of the pass.
+        let frame_view = match &self.targets.scaled {
+            Some((texture, _)) => &texture.view,
+            None => &view,
+        };
+        let (color_view, resolve_target) = match &self.targets.msaa {
+            Some(msaa) => (&msaa.view, Some(frame_view)),
+            None => (frame_view, None),
+        };
+
         {
-            let render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
+                    view: color_view,
+                    resolve_target,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
+                            r: sky_colors.horizon.r as f64,
+                            g: sky_colors.horizon.g as f64,
+                            b: sky_colors.horizon.b as f64,
                             a: 1.0,
                         }),
                         store: wgpu::StoreOp::Store,
                     },
                 })],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture.view,
+                    view: &self.targets.depth.view,
                     depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
+                        load: wgpu::LoadOp::Clear(Texture::DEPTH_CLEAR),
                         store: wgpu::StoreOp::Store,
                     }),
                     stencil_ops: None,
@@ -254,9 +986,131 @@ impl Renderer {
                 occlusion_query_set: None,
                 timestamp_writes: None,
             });
-            self.voxels.draw(render_pass, &self.common_bg);
+            self.sky.draw(&mut render_pass, &self.common_bg);
+            self.voxels
+                .draw(&mut render_pass, &self.common_bg, self.shadows.bind_group());
+            self.outline.draw(&mut render_pass, &self.common_bg);
+        }
+
+        if let Some((_, scaled_bg)) = &self.targets.scaled {
+            let mut blit_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Blit Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            self.blit.draw(&mut blit_pass, scaled_bg);
         }
         self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        if let Some(output) = output {
+            output.present();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use image::RgbaImage;
+
+    use crate::{scene::Scene, settings::GraphicsSettings};
+
+    use super::{atlas::BlockTextureMode, RenderError, Renderer};
+
+    /// The assets of the workspace, which don't depend on the working directory of the tests.
+    const ASSETS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets");
+
+    /// Set this environment variable to overwrite the golden images with the current output.
+    const UPDATE_GOLDEN: &str = "EXPLORA_UPDATE_GOLDEN";
+
+    /// Compares `frame` against `assets/golden/<name>.png`.
+    ///
+    /// Different adapters rasterize slightly differently, so a small
+    /// amount of pixels is allowed to differ.
+    fn assert_matches_golden(frame: &RgbaImage, name: &str) {
+        let path = Path::new(ASSETS).join("golden").join(format!("{name}.png"));
+        if std::env::var_os(UPDATE_GOLDEN).is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            frame.save(&path).unwrap();
+            return;
+        }
+
+        let golden = image::open(&path)
+            .unwrap_or_else(|e| {
+                panic!(
+                    "Could not open `{}` ({}). Run with {}=1 to create it.",
+                    path.display(),
+                    e,
+                    UPDATE_GOLDEN
+                )
+            })
+            .into_rgba8();
+        assert_eq!(golden.dimensions(), frame.dimensions());
+
+        let different = golden
+            .pixels()
+            .zip(frame.pixels())
+            .filter(|(a, b)| a.0.iter().zip(b.0).any(|(a, b)| a.abs_diff(b) > 16))
+            .count();
+        let ratio = different as f32 / (golden.width() * golden.height()) as f32;
+        if ratio > 0.01 {
+            let actual = path.with_extension("actual.png");
+            let _ = frame.save(&actual);
+            panic!(
+                "{:.2}% of the pixels differ from `{}`, the frame was saved to `{}`",
+                ratio * 100.0,
+                path.display(),
+                actual.display()
+            );
+        }
+    }
+
+    /// Renders a small flat world, or returns `None` if there is no graphics adapter.
+    fn render_terrain(settings: &GraphicsSettings) -> Option<RgbaImage> {
+        let renderer = Renderer::headless(320, 240, settings, Path::new(ASSETS));
+        let mut renderer = match pollster::block_on(renderer) {
+            Ok(renderer) => renderer,
+            Err(RenderError::NoAdapter) => {
+                eprintln!("Skipping golden image test: no graphics adapter available");
+                return None;
+            }
+            Err(e) => panic!("{}", e),
+        };
+        let mut scene = Scene::with_render_distance(320.0 / 240.0, 1);
+        scene.clock_mut().set_time_of_day(0.35);
+
+        renderer.render(&mut scene).unwrap();
+        let frame = renderer
+            .capture_frame()
+            .expect("headless renderers can capture frames");
+        Some(frame)
+    }
+
+    #[test]
+    fn terrain_golden_image() {
+        if let Some(frame) = render_terrain(&GraphicsSettings::default()) {
+            assert_matches_golden(&frame, "terrain");
+        }
+    }
+
+    #[test]
+    fn block_atlas_matches_texture_array() {
+        let settings = GraphicsSettings {
+            block_textures: BlockTextureMode::Atlas,
+            ..Default::default()
+        };
+        if let Some(frame) = render_terrain(&settings) {
+            assert_matches_golden(&frame, "terrain");
+        }
     }
 }
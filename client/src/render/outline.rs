@@ -0,0 +1,159 @@
+use vek::{Aabb, Vec3};
+
+use super::{buffer::Buffer, texture::Texture};
+
+/// How far the outline is drawn outside of the block, so that it is not hidden by its faces.
+const OUTLINE_OFFSET: f32 = 0.002;
+
+/// Draws the edges of boxes, such as the block the player is looking at.
+pub struct Outline {
+    pipeline: wgpu::RenderPipeline,
+    /// The two ends of every edge of the boxes, in world space.
+    vertex_buffer: Buffer<[f32; 3]>,
+    /// The number of boxes to outline.
+    box_count: u32,
+}
+
+impl Outline {
+    /// The most boxes drawn at once: the targeted block and the player.
+    pub const MAX_BOXES: usize = 2;
+    /// The two ends of the 12 edges of a box.
+    const VERTEX_COUNT: u32 = 24;
+
+    pub fn new(
+        device: &wgpu::Device,
+        common_bg_layout: &wgpu::BindGroupLayout,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Outline Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                include_str!("../../../assets/shaders/outline.wgsl").into(),
+            ),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Outline Pipeline Layout"),
+            bind_group_layouts: &[common_bg_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Outline Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::all(),
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            // Hidden by the terrain in front of it, without hiding anything itself.
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::GreaterEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+
+        let vertex_buffer = Buffer::new(
+            device,
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            &[[0.0; 3]; Self::VERTEX_COUNT as usize * Self::MAX_BOXES],
+        );
+
+        Self {
+            pipeline,
+            vertex_buffer,
+            box_count: 0,
+        }
+    }
+
+    /// The box to outline the block at `pos`, slightly larger than the block.
+    pub fn block_box(pos: Vec3<i32>) -> Aabb<f32> {
+        let min = pos.map(|x| x as f32) - OUTLINE_OFFSET;
+        Aabb {
+            min,
+            max: min + 1.0 + 2.0 * OUTLINE_OFFSET,
+        }
+    }
+
+    /// Outlines the given boxes, or nothing if there are none. Only the first
+    /// [`Outline::MAX_BOXES`] are drawn.
+    pub fn set_boxes(&mut self, queue: &wgpu::Queue, boxes: &[Aabb<f32>]) {
+        let boxes = &boxes[..boxes.len().min(Self::MAX_BOXES)];
+        self.box_count = boxes.len() as u32;
+        let vertices = boxes
+            .iter()
+            .flat_map(|&aabb| Self::edges(aabb))
+            .collect::<Vec<_>>();
+        self.vertex_buffer.write(queue, &vertices);
+    }
+
+    /// The two ends of the 12 edges of a box.
+    fn edges(aabb: Aabb<f32>) -> [[f32; 3]; Self::VERTEX_COUNT as usize] {
+        let Aabb { min, max } = aabb;
+        let corner = |x: bool, y: bool, z: bool| {
+            [
+                if x { max.x } else { min.x },
+                if y { max.y } else { min.y },
+                if z { max.z } else { min.z },
+            ]
+        };
+
+        let mut edges = [[0.0; 3]; Self::VERTEX_COUNT as usize];
+        let mut i = 0;
+        for a in [false, true] {
+            for b in [false, true] {
+                // One edge along every axis.
+                for (start, end) in [
+                    (corner(false, a, b), corner(true, a, b)),
+                    (corner(a, false, b), corner(a, true, b)),
+                    (corner(a, b, false), corner(a, b, true)),
+                ] {
+                    edges[i] = start;
+                    edges[i + 1] = end;
+                    i += 2;
+                }
+            }
+        }
+        edges
+    }
+
+    pub fn draw<'pass>(
+        &'pass self,
+        frame: &mut wgpu::RenderPass<'pass>,
+        common_bg: &'pass wgpu::BindGroup,
+    ) {
+        if self.box_count == 0 {
+            return;
+        }
+        frame.set_pipeline(&self.pipeline);
+        frame.set_bind_group(0, common_bg, &[]);
+        frame.set_vertex_buffer(0, self.vertex_buffer.slice());
+        frame.draw(0..Self::VERTEX_COUNT * self.box_count, 0..1);
+    }
+}
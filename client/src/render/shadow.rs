@@ -0,0 +1,377 @@
+use serde::{Deserialize, Serialize};
+use vek::{FrustumPlanes, Mat4, Vec3, Vec4};
+
+use crate::camera::Camera;
+
+use super::{buffer::Buffer, texture::Texture};
+
+/// The maximum number of shadow cascades.
+pub const MAX_CASCADES: usize = 3;
+
+/// How far behind a cascade, towards the light, blocks can still cast shadows into it.
+const CASTER_DISTANCE: f32 = 256.0;
+
+/// Quality of the sun shadows.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShadowMode {
+    /// No shadows. Recommended for low-end hardware.
+    Off,
+    /// A single low resolution shadow map.
+    Low,
+    /// Cascaded shadow maps.
+    #[default]
+    High,
+}
+
+impl ShadowMode {
+    pub fn cascades(self) -> u32 {
+        match self {
+            ShadowMode::Off => 0,
+            ShadowMode::Low => 1,
+            ShadowMode::High => MAX_CASCADES as u32,
+        }
+    }
+
+    /// The width and height of each cascade.
+    pub fn resolution(self) -> u32 {
+        match self {
+            ShadowMode::Off => 1,
+            ShadowMode::Low => 1024,
+            ShadowMode::High => 2048,
+        }
+    }
+}
+
+/// Shadow data read by the terrain shader.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShadowUniforms {
+    /// Light view projection of each cascade.
+    matrices: [[[f32; 4]; 4]; MAX_CASCADES],
+    /// The view distance at which each cascade ends.
+    splits: [f32; 4],
+    cascade_count: u32,
+    /// The size of a shadow map texel in UV space.
+    texel_size: f32,
+    _padding: [u32; 2],
+}
+
+impl Default for ShadowUniforms {
+    fn default() -> Self {
+        Self {
+            matrices: [Mat4::identity().into_col_arrays(); MAX_CASCADES],
+            splits: [0.0; 4],
+            cascade_count: 0,
+            texel_size: 0.0,
+            _padding: [0, 0],
+        }
+    }
+}
+
+/// Renders the terrain from the point of view of the sun, so
+/// that the terrain shader can tell which fragments are in shadow.
+pub struct Shadows {
+    mode: ShadowMode,
+    uniforms: ShadowUniforms,
+    uniforms_buffer: Buffer<ShadowUniforms>,
+    /// Light view projection used while rendering each cascade.
+    cascade_buffers: Vec<Buffer<[[f32; 4]; 4]>>,
+    cascade_bgs: Vec<wgpu::BindGroup>,
+    cascade_bg_layout: wgpu::BindGroupLayout,
+    /// A view of every cascade of the shadow map, to render into.
+    layer_views: Vec<wgpu::TextureView>,
+    /// Layout of the resources sampled by the terrain shader.
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Shadows {
+    pub fn new(device: &wgpu::Device, mode: ShadowMode) -> Self {
+        let cascade_bg_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Shadow Cascade Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let cascade_buffers = (0..MAX_CASCADES)
+            .map(|_| {
+                Buffer::new(
+                    device,
+                    wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    &[Mat4::<f32>::identity().into_col_arrays()],
+                )
+            })
+            .collect::<Vec<_>>();
+        let cascade_bgs = cascade_buffers
+            .iter()
+            .map(|buffer| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Shadow Cascade Bind Group"),
+                    layout: &cascade_bg_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                })
+            })
+            .collect();
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Shadow Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count: None,
+                },
+            ],
+        });
+        let uniforms_buffer = Buffer::new(
+            device,
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            &[ShadowUniforms::default()],
+        );
+        let (layer_views, bind_group) =
+            Self::create_shadow_map(device, &bind_group_layout, &uniforms_buffer, mode);
+
+        Self {
+            mode,
+            uniforms: ShadowUniforms::default(),
+            uniforms_buffer,
+            cascade_buffers,
+            cascade_bgs,
+            cascade_bg_layout,
+            layer_views,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    fn create_shadow_map(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        uniforms_buffer: &Buffer<ShadowUniforms>,
+        mode: ShadowMode,
+    ) -> (Vec<wgpu::TextureView>, wgpu::BindGroup) {
+        // Even without shadows the terrain shader expects a shadow map to be bound.
+        // OpenGL cannot view a texture with a single layer as an array, so there are always two.
+        let (texture, layer_views) =
+            Texture::depth_array(device, mode.resolution(), mode.cascades().max(2));
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Shadow Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniforms_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+        });
+        (layer_views, bind_group)
+    }
+
+    pub fn mode(&self) -> ShadowMode {
+        self.mode
+    }
+
+    /// Changes the shadow quality, recreating the shadow map.
+    pub fn set_mode(&mut self, device: &wgpu::Device, mode: ShadowMode) {
+        if mode == self.mode {
+            return;
+        }
+        let (layer_views, bind_group) =
+            Self::create_shadow_map(device, &self.bind_group_layout, &self.uniforms_buffer, mode);
+        self.mode = mode;
+        self.layer_views = layer_views;
+        self.bind_group = bind_group;
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    pub fn cascade_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.cascade_bg_layout
+    }
+
+    pub fn cascade_bind_group(&self, cascade: usize) -> &wgpu::BindGroup {
+        &self.cascade_bgs[cascade]
+    }
+
+    /// The number of cascades to render this frame.
+    pub fn cascade_count(&self) -> usize {
+        self.uniforms.cascade_count as usize
+    }
+
+    /// Fits the cascades to the part of the camera frustum closer than `distance`.
+    ///
+    /// `light_dir` points towards the light casting the shadows.
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        camera: &Camera,
+        view: Mat4<f32>,
+        light_dir: Vec3<f32>,
+        distance: f32,
+    ) {
+        let count = self.mode.cascades() as usize;
+        let resolution = self.mode.resolution();
+        let splits = cascade_splits(camera.near_plane(), distance, count);
+
+        let mut uniforms = ShadowUniforms {
+            cascade_count: count as u32,
+            texel_size: 1.0 / resolution as f32,
+            ..Default::default()
+        };
+        let mut near = camera.near_plane();
+        for (cascade, far) in splits.into_iter().enumerate().take(count) {
+            let matrix = cascade_matrix(camera, view, near, far, light_dir, resolution);
+            uniforms.matrices[cascade] = matrix.into_col_arrays();
+            uniforms.splits[cascade] = far;
+            self.cascade_buffers[cascade].write(queue, &[matrix.into_col_arrays()]);
+            near = far;
+        }
+        self.uniforms = uniforms;
+        self.uniforms_buffer.write(queue, &[uniforms]);
+    }
+
+    /// Starts a depth-only render pass into the given cascade.
+    pub fn begin_pass<'pass>(
+        &'pass self,
+        encoder: &'pass mut wgpu::CommandEncoder,
+        cascade: usize,
+    ) -> wgpu::RenderPass<'pass> {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Shadow Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.layer_views[cascade],
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        })
+    }
+}
+
+/// Splits the view distance between the cascades, blending a logarithmic
+/// and a uniform distribution so that close cascades get more detail.
+fn cascade_splits(near: f32, far: f32, count: usize) -> [f32; MAX_CASCADES] {
+    const LOG_WEIGHT: f32 = 0.6;
+    let mut splits = [far; MAX_CASCADES];
+    for (i, split) in splits.iter_mut().enumerate().take(count) {
+        let t = (i + 1) as f32 / count as f32;
+        let log = near * (far / near).powf(t);
+        let uniform = near + (far - near) * t;
+        *split = uniform + (log - uniform) * LOG_WEIGHT;
+    }
+    splits
+}
+
+/// Computes an orthographic light projection enclosing the camera frustum between `near` and `far`.
+fn cascade_matrix(
+    camera: &Camera,
+    view: Mat4<f32>,
+    near: f32,
+    far: f32,
+    light_dir: Vec3<f32>,
+    resolution: u32,
+) -> Mat4<f32> {
+    let proj = Mat4::perspective_lh_zo(camera.fov(), camera.aspect(), near, far);
+    let inv = (proj * view).inverted();
+    let mut corners = [Vec3::zero(); 8];
+    for (i, corner) in corners.iter_mut().enumerate() {
+        let ndc = Vec4::new(
+            if i & 1 == 0 { -1.0 } else { 1.0 },
+            if i & 2 == 0 { -1.0 } else { 1.0 },
+            if i & 4 == 0 { 0.0 } else { 1.0 },
+            1.0,
+        );
+        let world = inv * ndc;
+        *corner = world.xyz() / world.w;
+    }
+    let center = corners.iter().copied().sum::<Vec3<f32>>() / corners.len() as f32;
+    // A bounding sphere keeps the projection size constant while the camera rotates.
+    let radius = corners
+        .iter()
+        .map(|corner| corner.distance(center))
+        .fold(0.0, f32::max);
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let light_view = Mat4::look_at_lh(Vec3::zero(), -light_dir, Vec3::unit_z());
+    let center = light_view.mul_point(center);
+    // Move in whole texels to avoid shimmering edges when the camera moves.
+    let texel = 2.0 * radius / resolution as f32;
+    let x = (center.x / texel).floor() * texel;
+    let y = (center.y / texel).floor() * texel;
+
+    let light_proj = Mat4::orthographic_lh_zo(FrustumPlanes {
+        left: x - radius,
+        right: x + radius,
+        bottom: y - radius,
+        top: y + radius,
+        near: center.z - radius - CASTER_DISTANCE,
+        far: center.z + radius,
+    });
+    light_proj * light_view
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cascade_splits, MAX_CASCADES};
+
+    #[test]
+    fn cascade_splits_test() {
+        let splits = cascade_splits(0.1, 100.0, MAX_CASCADES);
+        assert!(splits[0] > 0.1);
+        assert!(splits.windows(2).all(|w| w[0] < w[1]));
+        assert!((splits[MAX_CASCADES - 1] - 100.0).abs() < 1e-3);
+
+        assert!((cascade_splits(0.1, 100.0, 1)[0] - 100.0).abs() < 1e-3);
+    }
+}
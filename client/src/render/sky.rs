@@ -0,0 +1,225 @@
+use std::f32::consts::TAU;
+
+use common::chunk::Chunk;
+use vek::{Rgb, Vec3};
+
+use super::texture::Texture;
+
+/// Colours used to paint the sky dome and the distance fog.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SkyColors {
+    /// Colour straight above the camera.
+    pub zenith: Rgb<f32>,
+    /// Colour at the horizon. Terrain fades into this colour.
+    pub horizon: Rgb<f32>,
+}
+
+impl SkyColors {
+    const NIGHT: Self = Self {
+        zenith: Rgb::new(0.01, 0.01, 0.04),
+        horizon: Rgb::new(0.04, 0.05, 0.10),
+    };
+    const DUSK: Self = Self {
+        zenith: Rgb::new(0.20, 0.25, 0.45),
+        horizon: Rgb::new(0.90, 0.50, 0.30),
+    };
+    const DAY: Self = Self {
+        zenith: Rgb::new(0.25, 0.50, 0.90),
+        horizon: Rgb::new(0.70, 0.82, 0.95),
+    };
+
+    /// Computes the sky colours for a time of day in the `[0, 1)` range,
+    /// where `0.0` is midnight, `0.25` sunrise, `0.5` noon and `0.75` sunset.
+    pub fn at(time_of_day: f32) -> Self {
+        // Height of the sun in the sky, from -1 (midnight) to 1 (noon).
+        let sun_height = sun_dir(time_of_day).y;
+        if sun_height < 0.0 {
+            Self::lerp(Self::DUSK, Self::NIGHT, (-sun_height * 4.0).min(1.0))
+        } else {
+            Self::lerp(Self::DUSK, Self::DAY, (sun_height * 3.0).min(1.0))
+        }
+    }
+
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        Self {
+            zenith: Rgb::lerp(from.zenith, to.zenith, t),
+            horizon: Rgb::lerp(from.horizon, to.horizon, t),
+        }
+    }
+}
+
+/// The direction pointing towards the sun at the given time of day.
+///
+/// The sun rises in the east (+x) at `0.25`, is straight up at `0.5`
+/// and sets in the west at `0.75`. The moon is always opposite to it.
+pub fn sun_dir(time_of_day: f32) -> Vec3<f32> {
+    let angle = time_of_day.rem_euclid(1.0) * TAU;
+    // Tilt the orbit slightly so faces never receive exactly the same light.
+    Vec3::new(angle.sin(), -angle.cos(), 0.25).normalized()
+}
+
+/// Directional and ambient light applied to the terrain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lighting {
+    /// Direction pointing towards the sun.
+    pub sun_dir: Vec3<f32>,
+    /// Direction pointing towards the light that currently lights the
+    /// world. That is the sun during the day and the moon at night.
+    pub light_dir: Vec3<f32>,
+    pub light_color: Rgb<f32>,
+    pub ambient: Rgb<f32>,
+}
+
+impl Lighting {
+    const SUN_COLOR: Rgb<f32> = Rgb::new(1.0, 0.95, 0.85);
+    const MOON_COLOR: Rgb<f32> = Rgb::new(0.15, 0.18, 0.30);
+    const DAY_AMBIENT: Rgb<f32> = Rgb::new(0.45, 0.48, 0.55);
+    const NIGHT_AMBIENT: Rgb<f32> = Rgb::new(0.06, 0.07, 0.12);
+
+    pub fn at(time_of_day: f32) -> Self {
+        let sun_dir = sun_dir(time_of_day);
+        let (light_dir, light_color) = if sun_dir.y >= 0.0 {
+            (sun_dir, Self::SUN_COLOR)
+        } else {
+            (-sun_dir, Self::MOON_COLOR)
+        };
+        // Fade the light out as it approaches the horizon, so switching
+        // between the sun and the moon is not noticeable.
+        let intensity = (light_dir.y * 4.0).min(1.0);
+        let daylight = (sun_dir.y * 3.0 + 0.5).clamp(0.0, 1.0);
+        Self {
+            sun_dir,
+            light_dir,
+            light_color: light_color * intensity,
+            ambient: Rgb::lerp(Self::NIGHT_AMBIENT, Self::DAY_AMBIENT, daylight),
+        }
+    }
+}
+
+/// The distance range over which terrain fades into the horizon colour.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fog {
+    pub start: f32,
+    pub end: f32,
+}
+
+impl Fog {
+    /// Computes a fog range that fully hides the edge of the loaded terrain.
+    ///
+    /// `render_distance` is the number of chunks loaded around the camera.
+    pub fn from_render_distance(render_distance: u32) -> Self {
+        let end = (render_distance as f32 * Chunk::SIZE.x as f32).max(Chunk::SIZE.x as f32);
+        Self {
+            start: end * 0.5,
+            end,
+        }
+    }
+}
+
+/// Renders the sky gradient behind the terrain.
+pub struct Sky {
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Sky {
+    pub fn new(
+        device: &wgpu::Device,
+        common_bg_layout: &wgpu::BindGroupLayout,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Sky Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                include_str!("../../../assets/shaders/sky.wgsl").into(),
+            ),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sky Pipeline Layout"),
+            bind_group_layouts: &[common_bg_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sky Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::all(),
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            // The sky is drawn first and never occludes anything.
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+
+        Self { pipeline }
+    }
+
+    pub fn draw<'pass>(
+        &'pass self,
+        frame: &mut wgpu::RenderPass<'pass>,
+        common_bg: &'pass wgpu::BindGroup,
+    ) {
+        frame.set_pipeline(&self.pipeline);
+        frame.set_bind_group(0, common_bg, &[]);
+        // A single triangle covering the whole screen.
+        frame.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Fog, Lighting, SkyColors};
+
+    #[test]
+    fn sky_is_brighter_at_noon_than_at_midnight() {
+        let noon = SkyColors::at(0.5);
+        let midnight = SkyColors::at(0.0);
+        assert!(noon.zenith.sum() > midnight.zenith.sum());
+        assert!(noon.horizon.sum() > midnight.horizon.sum());
+        assert_eq!(SkyColors::at(1.5), noon);
+    }
+
+    #[test]
+    fn moon_lights_the_world_at_night() {
+        let noon = Lighting::at(0.5);
+        assert!(noon.light_dir.y > 0.9);
+        assert_eq!(noon.light_dir, noon.sun_dir);
+
+        let midnight = Lighting::at(0.0);
+        assert!(midnight.sun_dir.y < -0.9);
+        assert!(midnight.light_dir.y > 0.9);
+        assert!(midnight.light_color.sum() < noon.light_color.sum());
+        assert!(midnight.ambient.sum() < noon.ambient.sum());
+    }
+
+    #[test]
+    fn fog_ends_at_render_distance() {
+        let fog = Fog::from_render_distance(4);
+        assert_eq!(fog.end, 64.0);
+        assert!(fog.start < fog.end);
+        assert!(Fog::from_render_distance(0).end > 0.0);
+    }
+}
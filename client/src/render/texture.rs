@@ -1,4 +1,36 @@
 use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+
+/// How block textures are filtered when they are drawn smaller than their size.
+///
+/// Textures drawn larger than their size always use the nearest texel, to keep them sharp.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextureFilter {
+    /// Uses the nearest texel of the nearest mipmap level. Sharp, but noisy in the distance.
+    Nearest,
+    /// Blends the nearest texels of the nearest mipmap level.
+    Bilinear,
+    /// Blends between mipmap levels as well. The smoothest.
+    #[default]
+    Trilinear,
+}
+
+impl TextureFilter {
+    fn min_filter(self) -> wgpu::FilterMode {
+        match self {
+            TextureFilter::Nearest => wgpu::FilterMode::Nearest,
+            TextureFilter::Bilinear | TextureFilter::Trilinear => wgpu::FilterMode::Linear,
+        }
+    }
+
+    fn mipmap_filter(self) -> wgpu::FilterMode {
+        match self {
+            TextureFilter::Nearest | TextureFilter::Bilinear => wgpu::FilterMode::Nearest,
+            TextureFilter::Trilinear => wgpu::FilterMode::Linear,
+        }
+    }
+}
 
 pub struct Texture {
     pub(crate) view: wgpu::TextureView,
@@ -6,7 +38,15 @@ pub struct Texture {
 }
 
 impl Texture {
-    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, image: &RgbaImage) -> Self {
+    /// Creates a texture from its mipmap levels, starting with the full size image.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mipmaps: &[RgbaImage],
+        format: wgpu::TextureFormat,
+        filter: TextureFilter,
+    ) -> Self {
+        let image = &mipmaps[0];
         let size = wgpu::Extent3d {
             width: image.width(),
             height: image.height(),
@@ -16,47 +56,136 @@ impl Texture {
         let handle = device.create_texture(&wgpu::TextureDescriptor {
             label: None,
             size,
-            mip_level_count: 1,
+            mip_level_count: mipmaps.len() as u32,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            format,
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         });
 
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &handle,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            image,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * image.width()),
-                rows_per_image: Some(image.height()),
-            },
-            size,
-        );
+        for (level, image) in mipmaps.iter().enumerate() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &handle,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                image,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * image.width()),
+                    rows_per_image: Some(image.height()),
+                },
+                wgpu::Extent3d {
+                    width: image.width(),
+                    height: image.height(),
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
 
         let view = handle.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
+        let sampler = Self::sampler(device, filter);
+
+        Self { view, sampler }
+    }
+
+    /// Creates a texture array, from the mipmap levels of every layer.
+    pub fn new_array(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layers: &[Vec<RgbaImage>],
+        format: wgpu::TextureFormat,
+        filter: TextureFilter,
+    ) -> Self {
+        let image = &layers[0][0];
+        // OpenGL guesses how textures are viewed from their number of layers: a single
+        // layer is not an array, and a multiple of 6 layers is a cube map. Add an unused
+        // layer in those cases.
+        let mut layer_count = layers.len() as u32;
+        if layer_count == 1 || layer_count.is_multiple_of(6) {
+            layer_count += 1;
+        }
+        let handle = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Texture Array"),
+            size: wgpu::Extent3d {
+                width: image.width(),
+                height: image.height(),
+                depth_or_array_layers: layer_count,
+            },
+            mip_level_count: layers[0].len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (layer, mipmaps) in layers.iter().enumerate() {
+            for (level, image) in mipmaps.iter().enumerate() {
+                queue.write_texture(
+                    wgpu::ImageCopyTexture {
+                        texture: &handle,
+                        mip_level: level as u32,
+                        origin: wgpu::Origin3d {
+                            x: 0,
+                            y: 0,
+                            z: layer as u32,
+                        },
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    image,
+                    wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(4 * image.width()),
+                        rows_per_image: Some(image.height()),
+                    },
+                    wgpu::Extent3d {
+                        width: image.width(),
+                        height: image.height(),
+                        depth_or_array_layers: 1,
+                    },
+                );
+            }
+        }
+
+        let view = handle.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
             ..Default::default()
         });
+        let sampler = Self::sampler(device, filter);
 
         Self { view, sampler }
     }
 
+    /// Creates a sampler for block textures.
+    ///
+    /// Textures repeat, so that a face can span several blocks.
+    pub fn sampler(device: &wgpu::Device, filter: TextureFilter) -> wgpu::Sampler {
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: filter.min_filter(),
+            mipmap_filter: filter.mipmap_filter(),
+            ..Default::default()
+        })
+    }
+
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    /// The depth the camera's depth buffer is cleared to, infinitely far away
+    /// since its depth is reversed.
+    pub const DEPTH_CLEAR: f32 = 0.0;
 
-    pub fn depth(device: &wgpu::Device, width: u32, height: u32) -> Self {
+    pub fn depth(device: &wgpu::Device, width: u32, height: u32, sample_count: u32) -> Self {
+        // Multisampled textures can't be sampled like regular ones.
+        let mut usage = wgpu::TextureUsages::RENDER_ATTACHMENT;
+        if sample_count == 1 {
+            usage |= wgpu::TextureUsages::TEXTURE_BINDING;
+        }
         let size = wgpu::Extent3d {
             width,
             height,
@@ -66,15 +195,100 @@ impl Texture {
             label: None,
             size,
             mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::DEPTH_FORMAT,
+            usage,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = Self::depth_sampler(device);
+        Self { view, sampler }
+    }
+
+    /// Creates an array of square depth textures, used for shadow maps.
+    ///
+    /// Returns the texture to be sampled as a whole, and a view
+    /// of every layer to render into.
+    pub fn depth_array(
+        device: &wgpu::Device,
+        size: u32,
+        layers: u32,
+    ) -> (Self, Vec<wgpu::TextureView>) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Depth Texture Array"),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: layers,
+            },
+            mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: Self::DEPTH_FORMAT,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
         });
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let layer_views = (0..layers)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let sampler = Self::depth_sampler(device);
+        (Self { view, sampler }, layer_views)
+    }
 
+    /// Creates a texture that can be rendered into, and then sampled if it has a single sample.
+    pub fn render_target(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let mut usage = wgpu::TextureUsages::RENDER_ATTACHMENT;
+        if sample_count == 1 {
+            usage |= wgpu::TextureUsages::TEXTURE_BINDING;
+        }
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Render Target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // Used to scale the frame up or down to the window size.
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        Self { view, sampler }
+    }
+
+    fn depth_sampler(device: &wgpu::Device) -> wgpu::Sampler {
+        device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
@@ -85,7 +299,6 @@ impl Texture {
             lod_min_clamp: 0.0,
             lod_max_clamp: 100.0,
             ..Default::default()
-        });
-        Self { view, sampler }
+        })
     }
 }
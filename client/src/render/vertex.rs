@@ -1,24 +1,38 @@
 use bytemuck::{Pod, Zeroable};
+use common::face::Face;
 use vek::Vec3;
 
 #[derive(Clone, Copy, Pod, Zeroable)]
 #[repr(C)]
 pub struct TerrainVertex {
     data: u32,
+    /// Per-face attributes: the face index in the lowest 3 bits,
+    /// followed by 4 bits of sky light and 4 bits of block light.
+    attrs: u32,
 }
 
 impl TerrainVertex {
-    pub fn new(pos: Vec3<f32>, texture_id: u32) -> Self {
+    pub fn new(
+        pos: Vec3<f32>,
+        texture_id: u32,
+        face: Face,
+        sky_light: u8,
+        block_light: u8,
+    ) -> Self {
         Self {
             data: ((pos.x as u32)
                 | ((pos.y as u32) << 5)
                 | ((pos.z as u32) << 14)
                 | (texture_id << 19)),
+            attrs: (face.index() & 0x7)
+                | ((sky_light as u32 & 0xf) << 3)
+                | ((block_light as u32 & 0xf) << 7),
         }
     }
 
     pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
-        const ATTRS: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![0 => Uint32];
+        const ATTRS: [wgpu::VertexAttribute; 2] =
+            wgpu::vertex_attr_array![0 => Uint32, 1 => Uint32];
         wgpu::VertexBufferLayout {
             step_mode: wgpu::VertexStepMode::Vertex,
             attributes: &ATTRS,
@@ -30,13 +44,14 @@ impl TerrainVertex {
 #[cfg(test)]
 mod tests {
 
+    use common::face::Face;
     use vek::Vec3;
 
     use super::TerrainVertex;
 
     #[test]
     fn test_vertex_data_compression() {
-        let vertex = TerrainVertex::new(Vec3::new(16.0, 256.0, 16.0), 999);
+        let vertex = TerrainVertex::new(Vec3::new(16.0, 256.0, 16.0), 999, Face::Bottom, 15, 7);
 
         let expected_x = vertex.data & 0x1f;
         let expected_y = (vertex.data >> 5) & 0x1ff;
@@ -47,5 +62,8 @@ mod tests {
         assert_eq!(expected_y, 256);
         assert_eq!(expected_z, 16);
         assert_eq!(expected_texture_id, 999);
+        assert_eq!(vertex.attrs & 0x7, Face::Bottom.index());
+        assert_eq!((vertex.attrs >> 3) & 0xf, 15);
+        assert_eq!((vertex.attrs >> 7) & 0xf, 7);
     }
 }
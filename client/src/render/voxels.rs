@@ -1,18 +1,23 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use common::chunk::Chunk;
+use common::terrain::Terrain;
 use vek::Vec2;
 
 use crate::block::BlockMap;
 
 use super::{
-    atlas::BlockAtlas, buffer::Buffer, mesh::create_chunk_mesh, texture::Texture,
+    atlas::{BlockAtlas, BlockTextureMode},
+    buffer::Buffer,
+    mesh::create_chunk_mesh,
+    shadow::Shadows,
+    texture::Texture,
     vertex::TerrainVertex,
 };
 
 pub struct TerrainGeometry {
     vertex_buffer: Buffer<TerrainVertex>,
-    chunk_pos_buffer: Buffer<[i32; 2]>,
+    // Never read, kept alive for as long as `bind_group` uses it.
+    _chunk_pos_buffer: Buffer<[i32; 2]>,
     bind_group: wgpu::BindGroup,
 }
 
@@ -26,7 +31,7 @@ impl TerrainGeometry {
         let vertex_buffer = Buffer::new(
             device,
             wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-            &vertices,
+            vertices,
         );
         let offset_buffer = Buffer::new(device, wgpu::BufferUsages::UNIFORM, &[pos.into_array()]);
         let chunk_pos_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -40,7 +45,7 @@ impl TerrainGeometry {
 
         Self {
             vertex_buffer,
-            chunk_pos_buffer: offset_buffer,
+            _chunk_pos_buffer: offset_buffer,
             bind_group: chunk_pos_bind_group,
         }
     }
@@ -48,7 +53,12 @@ impl TerrainGeometry {
 
 pub struct Voxels {
     index_buffer: Buffer<u32>,
+    shader: wgpu::ShaderModule,
+    terrain_pipeline_layout: wgpu::PipelineLayout,
     terrain_pipeline: wgpu::RenderPipeline,
+    /// Renders the terrain depth into a shadow map.
+    shadow_pipeline: wgpu::RenderPipeline,
+    chunk_pos_bg_layout: wgpu::BindGroupLayout,
     geometry: HashMap<Vec2<i32>, TerrainGeometry>,
 }
 
@@ -56,14 +66,24 @@ impl Voxels {
     pub fn new(
         device: &wgpu::Device,
         common_bg_layout: &wgpu::BindGroupLayout,
+        shadows: &Shadows,
         config: &wgpu::SurfaceConfiguration,
-        block_atlas: &BlockAtlas,
-        block_map: &BlockMap,
+        sample_count: u32,
+        block_textures: BlockTextureMode,
     ) -> Self {
+        let sample_block = match block_textures {
+            BlockTextureMode::Atlas => include_str!("../../../assets/shaders/block_atlas.wgsl"),
+            BlockTextureMode::Array => include_str!("../../../assets/shaders/block_array.wgsl"),
+        };
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: None,
             source: wgpu::ShaderSource::Wgsl(
-                include_str!("../../../assets/shaders/voxels.wgsl").into(),
+                format!(
+                    "{}\n{}",
+                    include_str!("../../../assets/shaders/voxels.wgsl"),
+                    sample_block
+                )
+                .into(),
             ),
         });
 
@@ -83,86 +103,155 @@ impl Voxels {
             });
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: None,
-            bind_group_layouts: &[&common_bg_layout, &chunk_pos_bg_layout],
+            bind_group_layouts: &[
+                common_bg_layout,
+                &chunk_pos_bg_layout,
+                shadows.bind_group_layout(),
+            ],
             push_constant_ranges: &[],
         });
 
-        let terrain_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
+        let terrain_pipeline = create_terrain_pipeline(
+            device,
+            &pipeline_layout,
+            &shader,
+            config.format,
+            sample_count,
+        );
+        let shadow_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shadow Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                include_str!("../../../assets/shaders/shadow.wgsl").into(),
+            ),
+        });
+        let shadow_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Shadow Pipeline Layout"),
+                bind_group_layouts: &[shadows.cascade_bind_group_layout(), &chunk_pos_bg_layout],
+                push_constant_ranges: &[],
+            });
+        let shadow_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow Pipeline"),
+            layout: Some(&shadow_pipeline_layout),
             vertex: wgpu::VertexState {
-                module: &shader,
+                module: &shadow_shader,
                 entry_point: "vs_main",
                 buffers: &[TerrainVertex::desc()],
             },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::all(),
-                })],
-            }),
+            fragment: None,
             primitive: wgpu::PrimitiveState {
                 topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
                 front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
+                // Only back faces cast shadows, which keeps lit faces from shadowing themselves.
+                cull_mode: Some(wgpu::Face::Front),
+                ..Default::default()
             },
             depth_stencil: Some(wgpu::DepthStencilState {
                 format: Texture::DEPTH_FORMAT,
                 depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
+                depth_compare: wgpu::CompareFunction::LessEqual,
                 stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
+                bias: wgpu::DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
             }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
+            multisample: wgpu::MultisampleState::default(),
             multiview: None,
         });
-        let mut geometry = HashMap::new();
-        let mut vertex_count = 0;
-        for z in 0..3 {
-            for x in 0..3 {
-                let chunk = Chunk::flat();
-                let offset = Vec2::new(z, x);
-                let mesh = create_chunk_mesh(&chunk, block_atlas, block_map);
 
-                let terrain = TerrainGeometry::new(device, &chunk_pos_bg_layout, &mesh, offset);
+        let index_buffer =
+            Buffer::new(device, wgpu::BufferUsages::INDEX, &compute_voxel_indices(4));
 
-                vertex_count += terrain.vertex_buffer.len();
+        Self {
+            shader,
+            terrain_pipeline_layout: pipeline_layout,
+            terrain_pipeline,
+            shadow_pipeline,
+            chunk_pos_bg_layout,
+            geometry: HashMap::new(),
+            index_buffer,
+        }
+    }
 
-                geometry.insert(offset, terrain);
+    /// Recreates the terrain pipeline to draw into targets with `sample_count` samples per pixel.
+    pub fn set_sample_count(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) {
+        self.terrain_pipeline = create_terrain_pipeline(
+            device,
+            &self.terrain_pipeline_layout,
+            &self.shader,
+            format,
+            sample_count,
+        );
+    }
+
+    /// Rebuilds the meshes of the `dirty` chunks.
+    ///
+    /// Chunks that are no longer part of the terrain are dropped.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        terrain: &Terrain,
+        dirty: HashSet<Vec2<i32>>,
+        block_atlas: &BlockAtlas,
+        block_map: &BlockMap,
+    ) {
+        for pos in dirty {
+            let mesh = create_chunk_mesh(terrain, pos, block_atlas, block_map);
+            if mesh.is_empty() {
+                self.geometry.remove(&pos);
+                continue;
             }
+            let geometry = TerrainGeometry::new(device, &self.chunk_pos_bg_layout, &mesh, pos);
+            self.geometry.insert(pos, geometry);
         }
 
-        let indices = compute_voxel_indices(vertex_count as usize);
-        let index_buffer = Buffer::new(device, wgpu::BufferUsages::INDEX, &indices);
-
-        Self {
-            terrain_pipeline,
-            geometry,
-            index_buffer,
+        // The index buffer is shared by every chunk, so it must be large enough for the biggest one.
+        let max_vertices = self
+            .geometry
+            .values()
+            .map(|geometry| geometry.vertex_buffer.len())
+            .max()
+            .unwrap_or(0);
+        if max_vertices / 4 * 6 > self.index_buffer.len() {
+            let indices = compute_voxel_indices(max_vertices as usize);
+            self.index_buffer = Buffer::new(device, wgpu::BufferUsages::INDEX, &indices);
         }
     }
 
     pub fn draw<'pass>(
-        &'pass mut self,
-        mut frame: wgpu::RenderPass<'pass>,
+        &'pass self,
+        frame: &mut wgpu::RenderPass<'pass>,
         common_bg: &'pass wgpu::BindGroup,
+        shadow_bg: &'pass wgpu::BindGroup,
     ) {
         frame.set_pipeline(&self.terrain_pipeline);
         frame.set_bind_group(0, common_bg, &[]);
+        frame.set_bind_group(2, shadow_bg, &[]);
+        self.draw_chunks(frame);
+    }
+
+    /// Draws the terrain depth from the point of view of a shadow cascade.
+    pub fn draw_shadow<'pass>(
+        &'pass self,
+        frame: &mut wgpu::RenderPass<'pass>,
+        cascade_bg: &'pass wgpu::BindGroup,
+    ) {
+        frame.set_pipeline(&self.shadow_pipeline);
+        frame.set_bind_group(0, cascade_bg, &[]);
+        self.draw_chunks(frame);
+    }
+
+    fn draw_chunks<'pass>(&'pass self, frame: &mut wgpu::RenderPass<'pass>) {
         frame.set_index_buffer(self.index_buffer.slice(), wgpu::IndexFormat::Uint32);
 
-        for (pos, geometry) in &self.geometry {
+        for geometry in self.geometry.values() {
             frame.set_bind_group(1, &geometry.bind_group, &[]);
             frame.set_vertex_buffer(0, geometry.vertex_buffer.slice());
             frame.draw_indexed(0..geometry.vertex_buffer.len() / 4 * 6, 0, 0..1);
@@ -170,6 +259,56 @@ impl Voxels {
     }
 }
 
+fn create_terrain_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[TerrainVertex::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::all(),
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            // The camera's depth is reversed, closer is greater.
+            depth_compare: wgpu::CompareFunction::Greater,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
 fn compute_voxel_indices(number_of_vertices: usize) -> Vec<u32> {
     let mut indices = Vec::with_capacity(number_of_vertices * 6 / 4);
     for i in 0..number_of_vertices / 4 {
@@ -1,42 +1,687 @@
-use vek::Vec3;
+use std::collections::HashSet;
 
-use crate::camera::{Camera, Matrices};
+use common::{
+    block::BlockId,
+    chunk::Chunk,
+    clock::{FixedTimestep, WorldClock},
+    player::{MoveMode, Player, PlayerInput},
+    raycast::RaycastHit,
+    terrain::Terrain,
+};
+use vek::{Aabb, Vec2, Vec3};
+
+use crate::{
+    camera::{Camera, CameraEffects, CameraMode, CameraSettings, Matrices},
+    input::InputSettings,
+    settings::GraphicsSettings,
+};
 
 pub struct Scene {
     camera: Camera,
+    /// The player the camera is attached to.
+    player: Player,
+    /// Where the eyes of the player were before the last tick, to interpolate the camera.
+    previous_eye_pos: Vec3<f32>,
+    /// Runs the simulation at a fixed rate, independently of the frame rate.
+    timestep: FixedTimestep,
+    /// The direction the player should move in, relative to the camera.
     movement_dir: Vec3<f32>,
+    /// The speed the player should move at, in blocks per second.
+    move_speed: f32,
+    /// How far the camera still has to turn, in degrees.
+    pending_look: Vec2<f32>,
+    /// How long the camera takes to follow the mouse, in seconds.
+    mouse_smoothing: f32,
+    /// The speed the player moves at without sprinting, in blocks per second.
+    normal_speed: f32,
+    /// Whether the player is zooming in.
+    zooming: bool,
+    /// Screen shake and head bob.
+    effects: CameraEffects,
+    camera_settings: CameraSettings,
+    clock: WorldClock,
+    /// Seconds since the scene was created.
+    time: f64,
+    terrain: Terrain,
+    /// Chunks whose mesh needs to be rebuilt.
+    dirty_chunks: HashSet<Vec2<i32>>,
+    /// The number of chunks loaded in every direction around the origin.
+    render_distance: u32,
+    /// The block the camera is looking at.
+    target: Option<RaycastHit>,
+    /// The block placed by [`Scene::place_block`].
+    selected_block: BlockId,
 }
 
-// TODO: make this configurable
-const FLY_CAMERA_SPEED: f32 = 7.0;
+/// How far away blocks can be targeted, in blocks.
+const REACH: f32 = 8.0;
+/// How far the third person and orbit cameras stay from the terrain behind them.
+const VIEW_MARGIN: f32 = 0.2;
+/// Falling faster than this when landing shakes the view, in blocks per second.
+const LANDING_SHAKE_SPEED: f32 = 15.0;
+/// The player is brought back to the spawn point after falling this far below
+/// the world, for example after walking past the loaded chunks.
+const RESPAWN_DEPTH: f32 = -64.0;
+/// Where the player's eyes are when the game starts, and after falling out of the
+/// world. Inside the chunk at the origin, which is always loaded.
+const SPAWN_EYE_POS: Vec3<f32> = Vec3::new(0.5, Chunk::SIZE.y as f32 + 2.0, 0.5);
+/// How many times per second the player and the world are simulated.
+pub const TICK_RATE: f32 = 60.0;
+
+/// The blocks that can be selected for placing, in the order of the number keys.
+pub const PLACEABLE_BLOCKS: [BlockId; 4] =
+    [BlockId::Dirt, BlockId::Stone, BlockId::Grass, BlockId::Lamp];
 
 impl Scene {
-    pub fn new(aspect: f32) -> Self {
-        Self {
+    pub fn new(aspect: f32, settings: &GraphicsSettings) -> Self {
+        let mut scene = Self::with_render_distance(aspect, settings.render_distance);
+        scene.apply_settings(settings);
+        scene
+    }
+
+    /// Creates a scene loading `render_distance` chunks in every direction around the origin.
+    pub fn with_render_distance(aspect: f32, render_distance: u32) -> Self {
+        let mut scene = Self {
             movement_dir: Vec3::zero(),
+            move_speed: 0.0,
+            pending_look: Vec2::zero(),
+            mouse_smoothing: 0.0,
+            normal_speed: 0.0,
+            zooming: false,
+            effects: CameraEffects::default(),
+            camera_settings: CameraSettings::default(),
+            player: Player::new(Vec3::zero()),
+            previous_eye_pos: Vec3::zero(),
+            timestep: FixedTimestep::new(TICK_RATE),
             camera: Camera::new(aspect),
+            clock: WorldClock::default(),
+            time: 0.0,
+            terrain: Terrain::new(),
+            dirty_chunks: HashSet::new(),
+            render_distance,
+            target: None,
+            selected_block: PLACEABLE_BLOCKS[0],
+        };
+        scene.respawn();
+        scene.apply_input_settings(&InputSettings::default());
+        scene
+            .camera
+            .set_fov_smoothing(scene.camera_settings.fov_smoothing);
+        scene.load_chunks();
+        scene
+    }
+
+    /// Loads the chunks within the render distance and unloads the ones past it.
+    fn load_chunks(&mut self) {
+        let chunks = self.render_distance as i32;
+        let out_of_range = self
+            .terrain
+            .chunks()
+            .map(|(pos, _)| pos)
+            .filter(|pos| pos.x.abs() > chunks || pos.y.abs() > chunks)
+            .collect::<Vec<_>>();
+        for pos in out_of_range {
+            self.terrain.remove(pos);
+            // The sides facing the removed chunk are now visible.
+            self.dirty_chunks.extend(Terrain::neighbour_chunks(pos));
+        }
+        let mut added = Vec::new();
+        for z in -chunks..=chunks {
+            for x in -chunks..=chunks {
+                let pos = Vec2::new(x, z);
+                if self.terrain.chunk(pos).is_none() {
+                    self.terrain.insert(pos, Chunk::flat());
+                    added.push(pos);
+                }
+            }
+        }
+        if !added.is_empty() {
+            let dirty = self.terrain.light_chunks(&added);
+            self.dirty_chunks.extend(dirty);
         }
     }
 
-    pub fn look(&mut self, delta_pitch: f32, delta_yaw: f32) {
-        self.camera.rotate_by(delta_pitch * 0.1, delta_yaw * 0.1);
+    /// Applies the settings that affect the scene, such as the field of view.
+    pub fn apply_settings(&mut self, settings: &GraphicsSettings) {
+        self.camera.set_fov(settings.fov.to_radians());
+        self.camera.set_fov_smoothing(settings.camera.fov_smoothing);
+        self.camera_settings = settings.camera.clone();
+        if settings.render_distance != self.render_distance {
+            self.render_distance = settings.render_distance;
+            self.load_chunks();
+        }
+    }
+
+    /// Applies the settings that affect how the player and the camera move.
+    pub fn apply_input_settings(&mut self, settings: &InputSettings) {
+        self.move_speed = settings.movement.speed;
+        self.normal_speed = settings.movement.speed;
+        self.player.acceleration = settings.movement.acceleration;
+        self.player.deceleration = settings.movement.deceleration;
+        self.mouse_smoothing = settings.mouse_smoothing;
+    }
+
+    /// Turns the camera by `delta` degrees, where positive x turns right and positive y looks down.
+    ///
+    /// With mouse smoothing the camera turns over the next frames instead.
+    pub fn look(&mut self, delta: Vec2<f32>) {
+        self.pending_look += delta;
     }
 
+    /// Sets the direction the player moves in, relative to the camera: x is right,
+    /// y is up and z is forward. When walking, moving up jumps.
     pub fn set_movement_dir(&mut self, dir: Vec3<f32>) {
         self.movement_dir = dir;
     }
+
+    pub fn move_mode(&self) -> MoveMode {
+        self.player.mode()
+    }
+
+    /// Switches between walking and flying.
+    pub fn toggle_fly(&mut self) {
+        let mode = match self.player.mode() {
+            MoveMode::Walk => MoveMode::Fly,
+            MoveMode::Fly => MoveMode::Walk,
+        };
+        self.player.set_mode(mode);
+    }
+
+    /// Zooms in while `zooming` is true.
+    pub fn set_zooming(&mut self, zooming: bool) {
+        self.zooming = zooming;
+    }
+
+    /// Shakes the view by `amount`, from 0 to 1, see [`CameraEffects::shake`].
+    pub fn shake(&mut self, amount: f32) {
+        self.effects.shake(amount);
+    }
+
+    /// Sets the speed the player moves at, in blocks per second.
+    pub fn set_move_speed(&mut self, speed: f32) {
+        self.move_speed = speed;
+    }
     pub fn resize(&mut self, w: f32, h: f32) {
         self.camera.set_aspect_ratio(w / h);
     }
 
-    pub fn tick(&mut self, dt: f32) {
-        let dx = self.movement_dir.x * FLY_CAMERA_SPEED * dt;
-        let dy = self.movement_dir.y * FLY_CAMERA_SPEED * dt;
-        let dz = self.movement_dir.z * FLY_CAMERA_SPEED * dt;
-        self.camera.move_by(dx, dy, dz);
+    /// Advances the scene by a frame lasting `dt` seconds.
+    ///
+    /// The camera turns every frame, but the simulation runs in ticks of a fixed
+    /// duration. The camera is placed between the last two ticks, so that it moves
+    /// smoothly at any frame rate.
+    pub fn update(&mut self, dt: f32) {
+        self.update_look(dt);
+        for _ in 0..self.timestep.advance(dt) {
+            self.tick();
+        }
+        let eye_pos = Vec3::lerp(
+            self.previous_eye_pos,
+            self.player.eye_pos(),
+            self.timestep.alpha(),
+        );
+        self.camera.set_pos(eye_pos);
+        self.update_camera_effects(dt);
+        self.update_view_distance();
+        self.time += dt as f64;
+        self.update_target();
+    }
+
+    /// Simulates the player and the world for one tick.
+    fn tick(&mut self) {
+        let dt = self.timestep.step();
+        self.previous_eye_pos = self.player.eye_pos();
+        let fall_speed = -self.player.velocity.y;
+        self.move_player(dt);
+        if self.player.pos.y < RESPAWN_DEPTH {
+            self.respawn();
+        }
+        if self.player.on_ground() && fall_speed > LANDING_SHAKE_SPEED {
+            self.shake((fall_speed - LANDING_SHAKE_SPEED) / 30.0);
+        }
+        self.clock.tick(dt);
+    }
+
+    /// Brings the player back to the spawn point, standing still.
+    fn respawn(&mut self) {
+        self.player.pos = SPAWN_EYE_POS - Vec3::unit_y() * Player::EYE_HEIGHT;
+        self.player.velocity = Vec3::zero();
+        // The camera jumps there instead of moving through the world.
+        self.previous_eye_pos = self.player.eye_pos();
+    }
+
+    /// Zooms, and shakes or bobs the view.
+    fn update_camera_effects(&mut self, dt: f32) {
+        let velocity = self.player.velocity;
+        let speed = Vec2::new(velocity.x, velocity.z).magnitude();
+        let fov_scale = if self.zooming {
+            self.camera_settings.zoom_fov_scale
+        } else if speed > self.normal_speed * 1.1 {
+            self.camera_settings.sprint_fov_scale
+        } else {
+            1.0
+        };
+        self.camera.set_fov_scale(fov_scale);
+        self.camera.update(dt);
+
+        let walk_speed = if self.player.on_ground() { speed } else { 0.0 };
+        self.effects.update(dt, walk_speed);
+        let bob = self.camera.mode() == CameraMode::FirstPerson;
+        let offset = self
+            .effects
+            .offset(&self.camera_settings, self.camera.right(), bob);
+        self.camera.set_offset(offset);
+    }
+
+    /// Turns the camera towards where the mouse moved it.
+    fn update_look(&mut self, dt: f32) {
+        let look = if self.mouse_smoothing > 0.0 {
+            // Catches up exponentially, the same way whatever the frame rate.
+            self.pending_look * (1.0 - (-dt / self.mouse_smoothing).exp())
+        } else {
+            self.pending_look
+        };
+        self.pending_look -= look;
+        self.camera.rotate_by(look.x, look.y);
+    }
+
+    /// Moves the player where it wants to go.
+    fn move_player(&mut self, dt: f32) {
+        let mut dir = self.movement_dir;
+        // Going up diagonally is not faster, walking and jumping at the same time is.
+        if self.player.mode() == MoveMode::Fly && dir.magnitude_squared() > 1.0 {
+            dir.normalize();
+        }
+        let input = PlayerInput {
+            velocity: self.camera.to_world(dir) * self.move_speed,
+            jump: dir.y > 0.0,
+        };
+        self.player.tick_in(dt, input, &self.terrain);
+    }
+
+    /// Brings the third person and orbit cameras closer to the player when
+    /// terrain is in the way, so that they don't look from inside it.
+    fn update_view_distance(&mut self) {
+        let max = self.camera.max_view_distance();
+        if max == 0.0 {
+            return;
+        }
+        let distance = self
+            .terrain
+            .raycast(self.camera.pos(), -self.camera.view_dir(), max)
+            .map_or(max, |hit| hit.distance - VIEW_MARGIN);
+        self.camera.set_view_distance(distance);
+    }
+
+    /// Switches to the next camera mode, see [`CameraMode::next`].
+    pub fn cycle_camera_mode(&mut self) {
+        self.camera.set_mode(self.camera.mode().next());
+        self.update_view_distance();
+    }
+
+    /// The box taken up by the player, where it is drawn this frame, if it can be
+    /// seen from the camera.
+    pub fn player_box(&self) -> Option<Aabb<f32>> {
+        if self.camera.mode() == CameraMode::FirstPerson {
+            return None;
+        }
+        let offset = self.camera.pos() - self.player.eye_pos();
+        let aabb = self.player.aabb();
+        Some(Aabb {
+            min: aabb.min + offset,
+            max: aabb.max + offset,
+        })
+    }
+
+    /// Finds the block the camera is looking at.
+    fn update_target(&mut self) {
+        self.target = self
+            .terrain
+            .raycast(self.camera.pos(), self.camera.forward(), REACH);
+    }
+
+    /// The block the camera is looking at, if there is one within reach.
+    pub fn target(&self) -> Option<RaycastHit> {
+        self.target
     }
 
     pub fn camera_matrices(&mut self) -> Matrices {
         self.camera.compute_matrices()
     }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn camera_pos(&self) -> Vec3<f32> {
+        self.camera.pos()
+    }
+
+    pub fn time_of_day(&self) -> f32 {
+        self.clock.time_of_day()
+    }
+
+    /// Seconds since the scene was created, used for animations.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn clock(&self) -> &WorldClock {
+        &self.clock
+    }
+
+    pub fn clock_mut(&mut self) -> &mut WorldClock {
+        &mut self.clock
+    }
+
+    pub fn terrain(&self) -> &Terrain {
+        &self.terrain
+    }
+
+    pub fn render_distance(&self) -> u32 {
+        self.render_distance
+    }
+
+    /// Replaces the block at `pos`, scheduling the affected chunks to be re-meshed.
+    ///
+    /// Returns `false` if `pos` is outside of the loaded terrain.
+    pub fn set_block(&mut self, pos: Vec3<i32>, block: BlockId) -> bool {
+        match self.terrain.set_block(pos, block) {
+            Some(dirty) => {
+                self.dirty_chunks.extend(dirty);
+                self.update_target();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The block placed by [`Scene::place_block`].
+    pub fn selected_block(&self) -> BlockId {
+        self.selected_block
+    }
+
+    pub fn select_block(&mut self, block: BlockId) {
+        self.selected_block = block;
+    }
+
+    /// Replaces the targeted block with air.
+    ///
+    /// Returns `false` if no block is targeted.
+    pub fn break_block(&mut self) -> bool {
+        match self.target {
+            Some(hit) => self.set_block(hit.pos, BlockId::Air),
+            None => false,
+        }
+    }
+
+    /// Places the selected block against the targeted face.
+    ///
+    /// Returns `false` if no block is targeted, or if the block would end up inside the player.
+    pub fn place_block(&mut self) -> bool {
+        let Some(hit) = self.target else {
+            return false;
+        };
+        let pos = hit.adjacent();
+        if self.player.intersects_block(pos) || !self.terrain.get(pos).is_some_and(BlockId::is_air)
+        {
+            return false;
+        }
+        self.set_block(pos, self.selected_block)
+    }
+
+    /// Takes the set of chunks that changed since the last call.
+    pub fn take_dirty_chunks(&mut self) -> HashSet<Vec2<i32>> {
+        std::mem::take(&mut self.dirty_chunks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{
+        block::BlockId,
+        chunk::Chunk,
+        face::Face,
+        player::{MoveMode, Player},
+        terrain::Terrain,
+    };
+    use vek::{Vec2, Vec3};
+
+    use crate::{input::InputSettings, settings::GraphicsSettings};
+
+    use crate::camera::CameraMode;
+
+    use super::{Scene, VIEW_MARGIN};
+
+    #[test]
+    fn break_and_place_test() {
+        let mut scene = Scene::with_render_distance(1.0, 1);
+        // Look straight down.
+        scene.look(Vec2::new(0.0, 90.0));
+        scene.update(0.0);
+        scene.take_dirty_chunks();
+
+        let hit = scene.target().expect("the ground is below the camera");
+        assert_eq!(hit.face, Face::Top);
+        let ground = hit.pos;
+
+        assert!(scene.break_block());
+        assert_eq!(scene.terrain().get(ground), Some(BlockId::Air));
+        let dirty = scene.take_dirty_chunks();
+        assert!(dirty.contains(&Terrain::chunk_pos(ground)));
+        // The block below is targeted now.
+        assert_eq!(scene.target().unwrap().pos, ground - Vec3::unit_y());
+
+        scene.select_block(BlockId::Lamp);
+        assert!(scene.place_block());
+        assert_eq!(scene.terrain().get(ground), Some(BlockId::Lamp));
+        assert!(!scene.take_dirty_chunks().is_empty());
+        assert_eq!(scene.target().unwrap().pos, ground);
+    }
+
+    #[test]
+    fn render_distance_test() {
+        let mut scene = Scene::with_render_distance(1.0, 1);
+        assert_eq!(scene.take_dirty_chunks().len(), 9 + 12);
+
+        // Only the new chunks and the ones next to them are meshed again.
+        let mut settings = GraphicsSettings {
+            render_distance: 2,
+            ..Default::default()
+        };
+        scene.apply_settings(&settings);
+        let dirty = scene.take_dirty_chunks();
+        assert!(dirty.contains(&Vec2::new(2, 2)));
+        assert!(dirty.contains(&Vec2::new(1, 0)));
+        assert!(!dirty.contains(&Vec2::zero()));
+
+        settings.render_distance = 1;
+        scene.apply_settings(&settings);
+        let dirty = scene.take_dirty_chunks();
+        assert!(dirty.contains(&Vec2::new(2, 0)));
+        assert!(dirty.contains(&Vec2::new(1, 0)));
+        assert!(!dirty.contains(&Vec2::zero()));
+        assert!(scene.terrain().chunk(Vec2::new(2, 0)).is_none());
+    }
+
+    #[test]
+    fn movement_test() {
+        let mut scene = Scene::with_render_distance(1.0, 0);
+        let settings = InputSettings::default();
+        let speed = settings.movement.speed;
+        scene.toggle_fly();
+        assert_eq!(scene.move_mode(), MoveMode::Fly);
+
+        // Diagonals are not faster.
+        scene.set_movement_dir(Vec3::new(1.0, 1.0, 1.0));
+        for _ in 0..100 {
+            scene.update(0.05);
+        }
+        assert!((scene.player.velocity.magnitude() - speed).abs() < 1e-3);
+
+        // Slowing down is the same with any frame rate.
+        scene.set_movement_dir(Vec3::zero());
+        let mut other = Scene::with_render_distance(1.0, 0);
+        other.toggle_fly();
+        other.player = scene.player.clone();
+        other.timestep = scene.timestep.clone();
+        let step = scene.timestep.step();
+        for _ in 0..21 {
+            scene.update(step / 2.0);
+        }
+        other.update(step * 10.5);
+        assert_eq!(scene.player.pos, other.player.pos);
+        assert_eq!(scene.player.velocity, other.player.velocity);
+        let expected = speed - settings.movement.deceleration * step * 10.0;
+        assert!((scene.player.velocity.magnitude() - expected).abs() < 1e-3);
+        // The camera is drawn halfway to the next tick.
+        assert!((scene.camera_pos() - other.camera_pos()).magnitude() < 1e-3);
+        let halfway = Vec3::lerp(scene.previous_eye_pos, scene.player.eye_pos(), 0.5);
+        assert!((scene.camera_pos() - halfway).magnitude() < 1e-3);
+    }
+
+    #[test]
+    fn walk_test() {
+        let mut scene = Scene::with_render_distance(1.0, 1);
+        assert_eq!(scene.move_mode(), MoveMode::Walk);
+        for _ in 0..20 {
+            scene.update(0.05);
+        }
+        // Standing on the grass.
+        let ground = scene
+            .terrain()
+            .raycast(scene.camera_pos(), -Vec3::unit_y(), 8.0);
+        let ground = ground.unwrap().pos;
+        assert_eq!(
+            scene.camera_pos().y,
+            ground.y as f32 + 1.0 + Player::EYE_HEIGHT
+        );
+
+        // Blocks can't be placed where the player stands.
+        scene.look(Vec2::new(0.0, 90.0));
+        scene.update(0.0);
+        assert_eq!(scene.target().unwrap().pos, ground);
+        assert!(!scene.place_block());
+    }
+
+    #[test]
+    fn respawn_test() {
+        let mut scene = Scene::with_render_distance(1.0, 0);
+        // Past the loaded chunks, the player falls out of the world and comes back.
+        scene.player.pos = Vec3::new(100.0, 10.0, 100.0);
+        for _ in 0..100 {
+            scene.update(0.05);
+        }
+        let pos = scene.player.pos;
+        assert_eq!((pos.x, pos.z), (0.5, 0.5));
+        assert_eq!(pos.y, Chunk::SIZE.y as f32);
+        assert!(scene.player.on_ground());
+    }
+
+    #[test]
+    fn mouse_smoothing_test() {
+        let mut scene = Scene::with_render_distance(1.0, 0);
+        let settings = InputSettings {
+            mouse_smoothing: 0.1,
+            ..Default::default()
+        };
+        scene.apply_input_settings(&settings);
+        let forward = scene.camera().forward();
+
+        scene.look(Vec2::new(0.0, 45.0));
+        scene.update(0.0);
+        assert_eq!(scene.camera().forward(), forward);
+        scene.update(0.1);
+        let halfway = scene.camera().forward();
+        assert!(halfway.y < forward.y);
+        for _ in 0..100 {
+            scene.update(0.1);
+        }
+        assert!(scene.camera().forward().y < halfway.y);
+        assert!(scene.pending_look.magnitude() < 1e-3);
+    }
+
+    #[test]
+    fn camera_mode_test() {
+        let mut scene = Scene::with_render_distance(1.0, 1);
+        for _ in 0..20 {
+            scene.update(0.05);
+        }
+        scene.look(Vec2::new(0.0, 90.0));
+        scene.update(0.0);
+        let eyes = scene.camera_pos();
+        let target = scene.target();
+        assert_eq!(scene.camera().view_pos(), eyes);
+        assert_eq!(scene.player_box(), None);
+
+        // Behind the player, which is looking down.
+        scene.cycle_camera_mode();
+        assert_eq!(scene.camera().mode(), CameraMode::ThirdPersonBack);
+        let view = scene.camera().view_pos();
+        assert!((view - (eyes + Vec3::unit_y() * 4.0)).magnitude() < 1e-3);
+        assert!(scene.player_box().unwrap().contains_point(eyes));
+        // Blocks are still targeted from the eyes of the player.
+        assert_eq!(scene.target(), target);
+
+        // In front of the player, which is in the ground.
+        scene.cycle_camera_mode();
+        let view = scene.camera().view_pos();
+        let ground = target.unwrap().pos.y as f32 + 1.0;
+        assert!((view.y - (ground + VIEW_MARGIN)).abs() < 1e-3);
+        assert!(scene.camera().view_dir().y > 0.99);
+
+        // Turning the orbit camera doesn't turn the player.
+        scene.cycle_camera_mode();
+        let forward = scene.camera().forward();
+        scene.look(Vec2::new(90.0, -45.0));
+        scene.update(0.0);
+        assert_eq!(scene.camera().forward(), forward);
+        assert!((scene.camera().view_pos() - eyes).magnitude() > 1.0);
+        assert_eq!(scene.target(), target);
+
+        scene.cycle_camera_mode();
+        assert_eq!(scene.camera().mode(), CameraMode::FirstPerson);
+        assert_eq!(scene.camera().view_pos(), eyes);
+    }
+
+    #[test]
+    fn camera_effects_test() {
+        let mut scene = Scene::with_render_distance(1.0, 1);
+        let fov = scene.camera().fov();
+
+        // Zooming narrows the field of view over a few frames.
+        scene.set_zooming(true);
+        scene.update(0.02);
+        assert!(scene.camera().fov() < fov);
+        assert!(scene.camera().fov() > fov * 0.3);
+        for _ in 0..50 {
+            scene.update(0.02);
+        }
+        assert!((scene.camera().fov() - fov * 0.3).abs() < 1e-3);
+        scene.set_zooming(false);
+        for _ in 0..50 {
+            scene.update(0.02);
+        }
+        assert_eq!(scene.camera().fov(), fov);
+
+        // Standing still, the view stays at the eyes.
+        assert_eq!(scene.camera().view_pos(), scene.camera_pos());
+        // Until it shakes, which fades away.
+        scene.shake(1.0);
+        scene.update(0.02);
+        assert!((scene.camera().view_pos() - scene.camera_pos()).magnitude() > 0.01);
+        for _ in 0..100 {
+            scene.update(0.02);
+        }
+        assert_eq!(scene.camera().view_pos(), scene.camera_pos());
+
+        // Walking bobs the view.
+        scene.set_movement_dir(Vec3::unit_z());
+        let mut bobbed: f32 = 0.0;
+        for _ in 0..50 {
+            scene.update(0.02);
+            bobbed = bobbed.max((scene.camera().view_pos() - scene.camera_pos()).magnitude());
+        }
+        assert!(bobbed > 0.01);
+    }
 }
@@ -0,0 +1,363 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use vek::{Aabr, Vec2};
+
+use crate::{
+    camera::CameraSettings,
+    render::{atlas::BlockTextureMode, shadow::ShadowMode, texture::TextureFilter},
+};
+
+/// The settings file loaded at startup, relative to the working directory.
+pub const SETTINGS_PATH: &str = "settings.toml";
+
+/// The file remembering the window between runs, relative to the working directory.
+pub const WINDOW_PATH: &str = "window.toml";
+
+/// How finished frames are shown on the screen.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PresentMode {
+    /// Waits for the display to refresh, no tearing. Always supported.
+    #[default]
+    Vsync,
+    /// Shows the latest frame at every refresh without blocking. Falls back to vsync.
+    Mailbox,
+    /// Shows frames as soon as they are ready, which can tear. Falls back to vsync.
+    Immediate,
+}
+
+impl PresentMode {
+    pub fn to_wgpu(self) -> wgpu::PresentMode {
+        match self {
+            PresentMode::Vsync => wgpu::PresentMode::Fifo,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    pub present_mode: PresentMode,
+    /// The number of samples per pixel used for anti-aliasing. 1 disables it.
+    pub msaa: u32,
+    /// The resolution the world is rendered at, relative to the window size.
+    pub render_scale: f32,
+    /// The vertical field of view in degrees.
+    pub fov: f32,
+    /// The number of chunks loaded in every direction around the origin.
+    pub render_distance: u32,
+    pub shadows: ShadowMode,
+    pub texture_filter: TextureFilter,
+    /// Only applied when the game starts.
+    pub block_textures: BlockTextureMode,
+    /// Directories laid out like `assets`, whose textures replace the default ones.
+    /// Later packs take priority. Only applied when the game starts.
+    pub resource_packs: Vec<PathBuf>,
+    pub camera: CameraSettings,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            present_mode: PresentMode::default(),
+            msaa: 1,
+            render_scale: 1.0,
+            fov: 90.0,
+            render_distance: 3,
+            shadows: ShadowMode::default(),
+            texture_filter: TextureFilter::default(),
+            block_textures: BlockTextureMode::default(),
+            resource_packs: Vec::new(),
+            camera: CameraSettings::default(),
+        }
+    }
+}
+
+impl GraphicsSettings {
+    pub const MIN_RENDER_SCALE: f32 = 0.25;
+    pub const MAX_RENDER_SCALE: f32 = 2.0;
+    pub const MIN_FOV: f32 = 30.0;
+    pub const MAX_FOV: f32 = 120.0;
+    pub const MAX_RENDER_DISTANCE: u32 = 16;
+
+    /// Loads the settings from a TOML file.
+    ///
+    /// Missing fields take their default value. If the file can not be read or parsed,
+    /// the error is logged and the default settings are used.
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        let settings = match std::fs::read_to_string(path) {
+            Ok(file) => match toml::from_str::<GraphicsSettings>(&file) {
+                Ok(settings) => settings,
+                Err(e) => {
+                    tracing::error!("Invalid settings file `{}`: {}", path.display(), e);
+                    Self::default()
+                }
+            },
+            Err(e) => {
+                tracing::info!(
+                    "Could not read `{}` ({}), using the default settings",
+                    path.display(),
+                    e
+                );
+                Self::default()
+            }
+        };
+        let settings = settings.validated();
+        tracing::info!(?settings, "Loaded graphics settings");
+        settings
+    }
+
+    /// Clamps every setting to the range supported by the game.
+    pub fn validated(mut self) -> Self {
+        if !matches!(self.msaa, 1 | 2 | 4 | 8) {
+            tracing::warn!("Unsupported MSAA sample count {}, disabling it", self.msaa);
+            self.msaa = 1;
+        }
+        if !self.render_scale.is_finite() {
+            self.render_scale = 1.0;
+        }
+        self.render_scale = self
+            .render_scale
+            .clamp(Self::MIN_RENDER_SCALE, Self::MAX_RENDER_SCALE);
+        if !self.fov.is_finite() {
+            self.fov = Self::default().fov;
+        }
+        self.fov = self.fov.clamp(Self::MIN_FOV, Self::MAX_FOV);
+        self.render_distance = self.render_distance.clamp(1, Self::MAX_RENDER_DISTANCE);
+        self.camera = self.camera.validated();
+        self
+    }
+}
+
+/// How the window covers the monitor in fullscreen.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FullscreenMode {
+    /// A borderless window the size of the monitor, which switches instantly.
+    #[default]
+    Borderless,
+    /// Changes the video mode of the monitor to the window size. Falls back to borderless.
+    Exclusive,
+}
+
+/// The state of the window, saved when the game exits and restored when it starts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowSettings {
+    pub fullscreen: bool,
+    pub fullscreen_mode: FullscreenMode,
+    /// The size of the window outside of fullscreen in pixels, also used as the
+    /// resolution in exclusive fullscreen.
+    pub width: u32,
+    pub height: u32,
+    /// The position of the top left corner of the window in pixels. The system
+    /// places the window if missing.
+    pub position: Option<[i32; 2]>,
+    /// The name of the monitor used in fullscreen. If missing or disconnected, the
+    /// monitor the window is on is used.
+    pub monitor: Option<String>,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        Self {
+            fullscreen: false,
+            fullscreen_mode: FullscreenMode::default(),
+            width: 1280,
+            height: 720,
+            position: None,
+            monitor: None,
+        }
+    }
+}
+
+impl WindowSettings {
+    pub const MIN_SIZE: u32 = 320;
+    pub const MAX_SIZE: u32 = 16384;
+    /// The pixels of the window that have to be on a monitor, in both directions,
+    /// to keep its position.
+    pub const MIN_VISIBLE: i32 = 64;
+
+    /// Loads the window state from a TOML file.
+    ///
+    /// Missing fields take their default value. If the file can not be read or parsed,
+    /// the default window is used.
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        let settings = match std::fs::read_to_string(path) {
+            Ok(file) => match toml::from_str::<WindowSettings>(&file) {
+                Ok(settings) => settings,
+                Err(e) => {
+                    tracing::error!("Invalid window file `{}`: {}", path.display(), e);
+                    Self::default()
+                }
+            },
+            Err(e) => {
+                tracing::info!(
+                    "Could not read `{}` ({}), using the default window",
+                    path.display(),
+                    e
+                );
+                Self::default()
+            }
+        };
+        let settings = settings.validated();
+        tracing::info!(?settings, "Loaded window settings");
+        settings
+    }
+
+    /// Saves the window state to a TOML file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let file = toml::to_string_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(path, file)
+    }
+
+    /// Forgets the window position if not enough of the window would be on any of
+    /// the `monitors`, for example after the monitor it was on was unplugged.
+    ///
+    /// Nothing is checked if the monitors are unknown.
+    pub fn check_position(&mut self, monitors: &[Aabr<i32>]) {
+        let Some([x, y]) = self.position else {
+            return;
+        };
+        let window = Aabr {
+            min: Vec2::new(x, y),
+            max: Vec2::new(x, y) + Vec2::new(self.width, self.height).as_(),
+        };
+        let visible = monitors.iter().any(|monitor| {
+            let overlap =
+                window.max.map2(monitor.max, i32::min) - window.min.map2(monitor.min, i32::max);
+            overlap.x >= Self::MIN_VISIBLE && overlap.y >= Self::MIN_VISIBLE
+        });
+        if !monitors.is_empty() && !visible {
+            tracing::info!(
+                "The window at {:?} is off-screen, letting the system place it",
+                self.position
+            );
+            self.position = None;
+        }
+    }
+
+    /// Clamps the window size to the range supported by the game.
+    pub fn validated(mut self) -> Self {
+        self.width = self.width.clamp(Self::MIN_SIZE, Self::MAX_SIZE);
+        self.height = self.height.clamp(Self::MIN_SIZE, Self::MAX_SIZE);
+        self
+    }
+}
+
+/// Parses a resolution written as `<width>x<height>`, such as `1920x1080`.
+pub fn parse_resolution(value: &str) -> Option<(u32, u32)> {
+    let (width, height) = value.split_once(['x', 'X'])?;
+    let width = width.trim().parse().ok()?;
+    let height = height.trim().parse().ok()?;
+    (width > 0 && height > 0).then_some((width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use vek::{Aabr, Vec2};
+
+    use crate::render::shadow::ShadowMode;
+
+    use super::{parse_resolution, FullscreenMode, GraphicsSettings, PresentMode, WindowSettings};
+
+    #[test]
+    fn parse_settings_test() {
+        let settings = toml::from_str::<GraphicsSettings>(
+            r#"
+            present_mode = "immediate"
+            msaa = 4
+            shadows = "low"
+            "#,
+        )
+        .unwrap()
+        .validated();
+        assert_eq!(settings.present_mode, PresentMode::Immediate);
+        assert_eq!(settings.msaa, 4);
+        assert_eq!(settings.shadows, ShadowMode::Low);
+        // Missing fields use their defaults.
+        assert_eq!(settings.fov, GraphicsSettings::default().fov);
+
+        let settings = GraphicsSettings {
+            msaa: 3,
+            render_scale: 10.0,
+            fov: 0.0,
+            render_distance: 0,
+            ..Default::default()
+        }
+        .validated();
+        assert_eq!(settings.msaa, 1);
+        assert_eq!(settings.render_scale, GraphicsSettings::MAX_RENDER_SCALE);
+        assert_eq!(settings.fov, GraphicsSettings::MIN_FOV);
+        assert_eq!(settings.render_distance, 1);
+    }
+
+    #[test]
+    fn window_settings_test() {
+        let settings = WindowSettings {
+            fullscreen: true,
+            fullscreen_mode: FullscreenMode::Exclusive,
+            width: 1920,
+            height: 1080,
+            position: Some([-1920, 40]),
+            monitor: Some("DP-1".to_string()),
+        };
+        let file = toml::to_string_pretty(&settings).unwrap();
+        assert_eq!(toml::from_str::<WindowSettings>(&file).unwrap(), settings);
+
+        let settings = toml::from_str::<WindowSettings>("width = 10")
+            .unwrap()
+            .validated();
+        assert_eq!(settings.width, WindowSettings::MIN_SIZE);
+        assert_eq!(settings.height, WindowSettings::default().height);
+        assert_eq!(settings.position, None);
+        assert_eq!(settings.monitor, None);
+    }
+
+    #[test]
+    fn window_position_test() {
+        let monitors = [
+            Aabr {
+                min: Vec2::new(0, 0),
+                max: Vec2::new(1920, 1080),
+            },
+            Aabr {
+                min: Vec2::new(-1280, 0),
+                max: Vec2::new(0, 1024),
+            },
+        ];
+        let mut settings = WindowSettings {
+            position: Some([-1000, 100]),
+            ..Default::default()
+        };
+        settings.check_position(&monitors);
+        assert_eq!(settings.position, Some([-1000, 100]));
+        // Mostly off the right edge, but enough of it is visible.
+        settings.position = Some([1800, 500]);
+        settings.check_position(&monitors);
+        assert_eq!(settings.position, Some([1800, 500]));
+
+        // A monitor that was unplugged.
+        settings.position = Some([2500, 100]);
+        settings.check_position(&monitors);
+        assert_eq!(settings.position, None);
+        // Only a sliver of it would be visible.
+        settings.position = Some([1900, 100]);
+        settings.check_position(&monitors);
+        assert_eq!(settings.position, None);
+    }
+
+    #[test]
+    fn parse_resolution_test() {
+        assert_eq!(parse_resolution("1920x1080"), Some((1920, 1080)));
+        assert_eq!(parse_resolution("800X600"), Some((800, 600)));
+        assert_eq!(parse_resolution("1920"), None);
+        assert_eq!(parse_resolution("0x600"), None);
+        assert_eq!(parse_resolution("widexhigh"), None);
+    }
+}
@@ -1,53 +1,112 @@
 use std::time::Instant;
 
-use vek::Vec2;
+use vek::{Aabr, Vec2};
 use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
     event::{DeviceEvent, KeyEvent},
     event_loop::{ControlFlow, EventLoop},
     keyboard::PhysicalKey,
-    window::{Window as WinitWindow, WindowBuilder},
+    monitor::{MonitorHandle, VideoMode},
+    window::{CursorGrabMode, Fullscreen, Window as WinitWindow, WindowBuilder},
 };
 
-use crate::{key_state::KeyState, render::Renderer, scene::Scene};
+#[cfg(feature = "gamepad")]
+use crate::gamepad::{GamepadEvent, Gamepads};
+use crate::{
+    input::{Action, Binding, InputSettings, InputState, INPUT_OVERRIDES_PATH, INPUT_PATH},
+    render::{error::RenderError, Renderer},
+    scene::{Scene, PLACEABLE_BLOCKS},
+    settings::{FullscreenMode, GraphicsSettings, WindowSettings, SETTINGS_PATH, WINDOW_PATH},
+};
+
+/// The steps of rebinding an action, each waiting for the next binding pressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rebinding {
+    /// Waiting for a binding of the action to rebind.
+    SelectAction,
+    /// Waiting for the binding replacing the one pressed to select the action.
+    SelectBinding(Action, Binding),
+}
 
 pub struct Window {
     cursor_grabbed: bool,
+    rebinding: Option<Rebinding>,
+    window_settings: WindowSettings,
+    settings: GraphicsSettings,
+    input_settings: InputSettings,
+    input: InputState,
+    #[cfg(feature = "gamepad")]
+    gamepads: Option<Gamepads>,
     scene: Scene,
+    // Declared before `window` so the renderer, which draws on it, is dropped first.
     renderer: Renderer,
     event_loop: Option<EventLoop<()>>,
     window: WinitWindow,
 }
 
 impl Window {
-    #[allow(clippy::new_without_default)]
-    pub fn new() -> Self {
+    /// Opens the window where it was when the game last exited.
+    ///
+    /// `resolution` replaces the remembered window size.
+    pub fn new(resolution: Option<(u32, u32)>) -> Result<Self, RenderError> {
         let event_loop = EventLoop::new().unwrap();
         // ControlFlow::Poll continuously runs the event loop, even if the OS hasn't
         // dispatched any events. This is ideal for games and similar applications.
         event_loop.set_control_flow(ControlFlow::Poll);
 
-        let window = WindowBuilder::new()
+        let mut window_settings = WindowSettings::load(WINDOW_PATH);
+        if let Some((width, height)) = resolution {
+            window_settings.width = width;
+            window_settings.height = height;
+            window_settings = window_settings.validated();
+        }
+        let monitors = event_loop
+            .available_monitors()
+            .map(|monitor| {
+                let (pos, size) = (monitor.position(), monitor.size());
+                Aabr {
+                    min: Vec2::new(pos.x, pos.y),
+                    max: Vec2::new(pos.x, pos.y) + Vec2::new(size.width, size.height).as_(),
+                }
+            })
+            .collect::<Vec<_>>();
+        window_settings.check_position(&monitors);
+        let size = PhysicalSize::new(window_settings.width, window_settings.height);
+        let mut builder = WindowBuilder::new()
             .with_title("explora")
-            .build(&event_loop)
-            .unwrap();
-        let renderer = pollster::block_on(Renderer::new(&window));
+            .with_inner_size(size);
+        if let Some([x, y]) = window_settings.position {
+            builder = builder.with_position(PhysicalPosition::new(x, y));
+        }
+        let window = builder.build(&event_loop).unwrap();
+        let settings = GraphicsSettings::load(SETTINGS_PATH);
+        let input_settings = InputSettings::load(INPUT_PATH, INPUT_OVERRIDES_PATH);
+        let renderer = pollster::block_on(Renderer::new(&window, &settings))?;
         let size = window.inner_size();
-        let scene = Scene::new(size.width as f32 / size.height as f32);
-        Self {
+        let mut scene = Scene::new(size.width as f32 / size.height.max(1) as f32, &settings);
+        scene.apply_input_settings(&input_settings);
+        let window = Self {
+            window_settings,
+            settings,
+            input_settings,
+            input: InputState::default(),
+            #[cfg(feature = "gamepad")]
+            gamepads: Gamepads::new(),
             window,
             event_loop: Some(event_loop),
             renderer,
             cursor_grabbed: false,
+            rebinding: None,
             scene,
-        }
+        };
+        window.apply_fullscreen();
+        Ok(window)
     }
 
     pub fn run(&mut self) {
         tracing::info!("Running explora");
 
         let mut last_frame = Instant::now();
-        let mut key_state = KeyState::default();
-        const SENSITIVITY: f32 = 100.0;
 
         let _ = self
             .event_loop
@@ -59,8 +118,13 @@ impl Window {
                 {
                     match event {
                         winit::event::WindowEvent::Resized(size) => {
-                            self.renderer.resize(size.width, size.height);
-                            self.scene.resize(size.width as f32, size.height as f32);
+                            self.resize(size.width, size.height);
+                        }
+                        // Only the window position is remembered, not the fullscreen one.
+                        winit::event::WindowEvent::Moved(position)
+                            if self.window.fullscreen().is_none() =>
+                        {
+                            self.window_settings.position = Some([position.x, position.y]);
                         }
                         winit::event::WindowEvent::CloseRequested => {
                             tracing::info!("Application close requested.");
@@ -68,8 +132,7 @@ impl Window {
                         }
                         winit::event::WindowEvent::ScaleFactorChanged { .. } => {
                             let size = self.window.inner_size();
-                            self.renderer.resize(size.width, size.height);
-                            self.scene.resize(size.width as f32, size.height as f32);
+                            self.resize(size.width, size.height);
                         }
 
                         winit::event::WindowEvent::KeyboardInput {
@@ -81,44 +144,325 @@ impl Window {
                                 },
                             ..
                         } => {
-                            key_state.update(code, state.is_pressed());
+                            self.handle_input(Binding::Key(code), state.is_pressed());
+                        }
+                        winit::event::WindowEvent::MouseInput { state, button, .. } => {
+                            if !self.cursor_grabbed && state.is_pressed() {
+                                // The click only brings the cursor back into the game.
+                                self.grab_cursor(true);
+                            } else {
+                                self.handle_input(Binding::Mouse(button), state.is_pressed());
+                            }
+                        }
+                        winit::event::WindowEvent::Focused(false) => {
+                            // Keys released while the window is in the background are never reported.
+                            self.input.clear();
+                            self.grab_cursor(false);
                         }
                         _ => (),
                     }
                 }
+                // The mouse is used for other windows while the cursor is free.
                 winit::event::Event::DeviceEvent {
                     event: DeviceEvent::MouseMotion { delta: (dx, dy) },
                     ..
-                } => {
-                    // map sensitivity to a range of 1 - 200. 100 being default.
-                    let delta = Vec2::new(
-                        dx as f32 * (SENSITIVITY / 100.0),
-                        dy as f32 * (SENSITIVITY / 100.0),
-                    );
-                    self.scene.look(delta.x, delta.y);
+                } if self.cursor_grabbed => {
+                    let delta = Vec2::new(dx as f32, dy as f32);
+                    self.scene.look(self.input_settings.mouse_look(delta));
                 }
 
+                // Every exit goes through here, including rendering errors.
+                winit::event::Event::LoopExiting => self.save_window_settings(),
+
                 winit::event::Event::AboutToWait => {
+                    #[cfg(feature = "gamepad")]
+                    self.poll_gamepads();
                     let dt = last_frame.elapsed();
-                    self.scene.set_movement_dir(key_state.dir());
-                    self.scene.tick(dt.as_secs_f32());
+                    self.scene
+                        .set_movement_dir(self.input.movement_dir(&self.input_settings));
+                    self.scene
+                        .set_move_speed(self.input.move_speed(&self.input_settings));
+                    self.scene
+                        .set_zooming(self.input.is_pressed(&self.input_settings, Action::Zoom));
+                    // The camera stays still while the cursor is free, as with the mouse.
+                    if self.cursor_grabbed {
+                        let look = self
+                            .input
+                            .look_delta(&self.input_settings, dt.as_secs_f32());
+                        if look != Vec2::zero() {
+                            self.scene.look(look);
+                        }
+                    }
+                    self.scene.update(dt.as_secs_f32());
                     last_frame = Instant::now();
-                    self.renderer.render(&mut self.scene);
+                    if let Err(e) = self.renderer.render(&mut self.scene) {
+                        tracing::error!("{}", e);
+                        elwt.exit();
+                    }
                 }
                 _ => (),
             });
     }
 
+    /// Updates the state of a key or mouse button, and performs the actions it triggers.
+    ///
+    /// While rebinding, the next binding pressed is captured instead.
+    fn handle_input(&mut self, binding: Binding, pressed: bool) {
+        let just_pressed = pressed && !self.input.is_held(binding);
+        let actions = self.input.update(&self.input_settings, binding, pressed);
+        if just_pressed {
+            if let Some(step) = self.rebinding.take() {
+                self.capture_binding(step, binding);
+                return;
+            }
+        }
+        for action in actions {
+            self.perform(action);
+        }
+    }
+
+    /// Uses `binding` for the current step of rebinding an action.
+    fn capture_binding(&mut self, step: Rebinding, binding: Binding) {
+        match step {
+            Rebinding::SelectAction => match self.input_settings.actions(binding).next() {
+                // Pressing the rebinding key again cancels.
+                Some(Action::Rebind) | None => tracing::info!("Cancelled rebinding"),
+                Some(action) => {
+                    tracing::info!("Press the new binding of {:?}", action);
+                    self.rebinding = Some(Rebinding::SelectBinding(action, binding));
+                }
+            },
+            Rebinding::SelectBinding(action, old) => {
+                self.rebind(action, Some(old), binding);
+                tracing::info!("Bound {:?} to `{}` instead of `{}`", action, binding, old);
+            }
+        }
+    }
+
+    /// Applies everything that happened on the gamepads since the last frame.
+    #[cfg(feature = "gamepad")]
+    fn poll_gamepads(&mut self) {
+        while let Some(event) = self.gamepads.as_mut().and_then(Gamepads::next_event) {
+            match event {
+                GamepadEvent::Button(button, pressed) => {
+                    self.handle_input(Binding::Gamepad(button), pressed)
+                }
+                GamepadEvent::Axis(axis, value) => self.input.set_axis(axis, value),
+                GamepadEvent::Disconnected => self.input.clear_gamepad(),
+            }
+        }
+    }
+
+    /// Performs an action that happens once when its binding is pressed.
+    ///
+    /// Movement is handled every frame instead, while the bindings are held down.
+    fn perform(&mut self, action: Action) {
+        match action {
+            Action::Break => {
+                self.scene.break_block();
+            }
+            Action::Place => {
+                self.scene.place_block();
+            }
+            Action::PauseTime => self.scene.clock_mut().toggle_paused(),
+            Action::SlowDownTime => {
+                let clock = self.scene.clock_mut();
+                clock.set_speed(clock.speed() / 2.0);
+            }
+            Action::SpeedUpTime => {
+                let clock = self.scene.clock_mut();
+                clock.set_speed(clock.speed() * 2.0);
+            }
+            Action::ToggleFly => {
+                self.scene.toggle_fly();
+                tracing::info!(mode = ?self.scene.move_mode(), "Changed movement mode");
+            }
+            Action::CycleCamera => {
+                self.scene.cycle_camera_mode();
+                tracing::info!(mode = ?self.scene.camera().mode(), "Changed camera mode");
+            }
+            Action::ReloadSettings => self.reload_settings(),
+            Action::Rebind => {
+                tracing::info!("Press a binding of the action to rebind");
+                self.rebinding = Some(Rebinding::SelectAction);
+            }
+            Action::ToggleFullscreen => self.set_fullscreen(!self.window_settings.fullscreen),
+            // There is no menu yet, the cursor is released to use other windows,
+            // and clicking in the window grabs it again.
+            Action::ToggleMenu => self.grab_cursor(!self.cursor_grabbed),
+            Action::SelectBlock1
+            | Action::SelectBlock2
+            | Action::SelectBlock3
+            | Action::SelectBlock4 => {
+                let slot = Action::SELECT_BLOCK.iter().position(|&a| a == action);
+                if let Some(&block) = slot.and_then(|i| PLACEABLE_BLOCKS.get(i)) {
+                    tracing::info!(?block, "Selected block");
+                    self.scene.select_block(block);
+                }
+            }
+            Action::MoveForward
+            | Action::MoveBackward
+            | Action::MoveLeft
+            | Action::MoveRight
+            | Action::Jump
+            | Action::Sneak
+            | Action::Sprint
+            | Action::MoveSlowly
+            | Action::Zoom => (),
+        }
+    }
+
+    /// Replaces the binding `old` of `action` with `binding`, or adds it without
+    /// `old`, and saves the bindings.
+    ///
+    /// Other actions bound to `binding` are unbound from it.
+    pub fn rebind(&mut self, action: Action, old: Option<Binding>, binding: Binding) {
+        for other in self.input_settings.rebind(action, old, binding) {
+            tracing::warn!("`{}` is no longer bound to {:?}", binding, other);
+        }
+        self.input.clear();
+        if let Err(e) = self
+            .input_settings
+            .save_overrides(INPUT_PATH, INPUT_OVERRIDES_PATH)
+        {
+            tracing::error!(
+                "Could not save the bindings to `{}`: {}",
+                INPUT_OVERRIDES_PATH,
+                e
+            );
+        }
+    }
+
+    /// Loads the settings and bindings files again and applies any change.
+    pub fn reload_settings(&mut self) {
+        let input_settings = InputSettings::load(INPUT_PATH, INPUT_OVERRIDES_PATH);
+        if input_settings != self.input_settings {
+            self.input.clear();
+            self.scene.apply_input_settings(&input_settings);
+            self.input_settings = input_settings;
+        }
+
+        let settings = GraphicsSettings::load(SETTINGS_PATH);
+        if settings != self.settings {
+            self.renderer.apply_settings(&settings);
+            self.scene.apply_settings(&settings);
+            self.settings = settings;
+        }
+    }
+
+    fn resize(&mut self, w: u32, h: u32) {
+        // Minimized windows have no size, keep the last one until they are restored.
+        if w == 0 || h == 0 {
+            return;
+        }
+        if self.window.fullscreen().is_none() {
+            self.window_settings.width = w;
+            self.window_settings.height = h;
+        }
+        self.renderer.resize(w, h);
+        self.scene.resize(w as f32, h as f32);
+    }
+
+    /// Switches between the window and fullscreen, in the mode chosen in the window settings.
+    pub fn set_fullscreen(&mut self, value: bool) {
+        self.window_settings.fullscreen = value;
+        self.apply_fullscreen();
+    }
+
+    fn apply_fullscreen(&self) {
+        let settings = &self.window_settings;
+        let fullscreen = settings.fullscreen.then(|| {
+            let monitor = self.monitor();
+            if settings.fullscreen_mode == FullscreenMode::Exclusive {
+                let size = PhysicalSize::new(settings.width, settings.height);
+                match monitor.as_ref().and_then(|m| closest_video_mode(m, size)) {
+                    Some(mode) => return Fullscreen::Exclusive(mode),
+                    None => tracing::warn!(
+                        "No video mode supports exclusive fullscreen, using borderless fullscreen"
+                    ),
+                }
+            }
+            Fullscreen::Borderless(monitor)
+        });
+        tracing::info!(
+            mode = ?fullscreen.as_ref().map(|_| settings.fullscreen_mode),
+            "Changed fullscreen mode"
+        );
+        self.window.set_fullscreen(fullscreen);
+    }
+
+    /// The monitor named in the window settings, or the one the window is on.
+    fn monitor(&self) -> Option<MonitorHandle> {
+        let named = self.window_settings.monitor.as_ref().and_then(|name| {
+            let monitor = self
+                .window
+                .available_monitors()
+                .find(|m| m.name().as_ref() == Some(name));
+            if monitor.is_none() {
+                tracing::warn!("Monitor `{}` is not connected", name);
+            }
+            monitor
+        });
+        named
+            .or_else(|| self.window.current_monitor())
+            .or_else(|| self.window.primary_monitor())
+    }
+
+    /// Remembers the window and the monitor it is on for the next run.
+    fn save_window_settings(&mut self) {
+        if let Some(name) = self.window.current_monitor().and_then(|m| m.name()) {
+            self.window_settings.monitor = Some(name);
+        }
+        if let Err(e) = self.window_settings.save(WINDOW_PATH) {
+            tracing::error!("Could not save the window to `{}`: {}", WINDOW_PATH, e);
+        }
+    }
+
+    /// Hides the cursor and keeps it in the window so the mouse turns the camera,
+    /// or releases it.
+    ///
+    /// The cursor is locked in place where supported, and otherwise confined to
+    /// the window. While it is released, moving the mouse doesn't turn the camera.
     pub fn grab_cursor(&mut self, value: bool) {
-        self.window.set_cursor_visible(!value);
-        let mode = if value {
-            winit::window::CursorGrabMode::Locked
-        } else {
-            winit::window::CursorGrabMode::None
-        };
-        match self.window.set_cursor_grab(mode) {
-            Ok(_) => self.cursor_grabbed = value,
-            Err(e) => tracing::warn!("Could not grab cursor in {:?} mode ({})", mode, e),
+        if value == self.cursor_grabbed {
+            return;
+        }
+        if value {
+            let grabbed = [CursorGrabMode::Locked, CursorGrabMode::Confined]
+                .into_iter()
+                .find(|&mode| match self.window.set_cursor_grab(mode) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        tracing::debug!("Could not grab the cursor in {:?} mode ({})", mode, e);
+                        false
+                    }
+                });
+            match grabbed {
+                Some(mode) => tracing::debug!("Grabbed the cursor in {:?} mode", mode),
+                None => {
+                    tracing::warn!("Could not grab the cursor");
+                    return;
+                }
+            }
+        } else if let Err(e) = self.window.set_cursor_grab(CursorGrabMode::None) {
+            tracing::warn!("Could not release the cursor ({})", e);
         }
+        self.window.set_cursor_visible(!value);
+        self.cursor_grabbed = value;
     }
 }
+
+/// The video mode of `monitor` with the resolution closest to `size`, and the
+/// highest refresh rate and bit depth among those.
+fn closest_video_mode(monitor: &MonitorHandle, size: PhysicalSize<u32>) -> Option<VideoMode> {
+    monitor.video_modes().min_by_key(|mode| {
+        let mode_size = mode.size();
+        let distance =
+            mode_size.width.abs_diff(size.width) + mode_size.height.abs_diff(size.height);
+        (
+            distance,
+            std::cmp::Reverse(mode.refresh_rate_millihertz()),
+            std::cmp::Reverse(mode.bit_depth()),
+        )
+    })
+}
@@ -4,6 +4,7 @@ pub enum BlockId {
     Dirt,
     Stone,
     Grass,
+    Lamp,
 }
 
 impl BlockId {
@@ -14,6 +15,19 @@ impl BlockId {
     pub const fn is_solid(self) -> bool {
         !self.is_air()
     }
+
+    /// Whether this block stops light from passing through it.
+    pub const fn is_opaque(self) -> bool {
+        self.is_solid()
+    }
+
+    /// The light level emitted by this block, from 0 to [`MAX_LIGHT`](crate::light::MAX_LIGHT).
+    pub const fn light_emission(self) -> u8 {
+        match self {
+            Self::Lamp => 14,
+            _ => 0,
+        }
+    }
 }
 
 impl From<&String> for BlockId {
@@ -22,6 +36,7 @@ impl From<&String> for BlockId {
             "dirt" => BlockId::Dirt,
             "stone" => BlockId::Stone,
             "grass" => BlockId::Grass,
+            "lamp" => BlockId::Lamp,
             _ => BlockId::Air,
         }
     }
@@ -4,11 +4,21 @@ use crate::block::BlockId;
 
 pub struct Chunk {
     blocks: [BlockId; Self::SIZE.x * Self::SIZE.y * Self::SIZE.z],
+    /// Sky light in the high nibble and block light in the low nibble.
+    light: [u8; Self::SIZE.x * Self::SIZE.y * Self::SIZE.z],
 }
 
 impl Chunk {
     pub const SIZE: Vec3<usize> = Vec3::new(16, 256, 16);
 
+    /// A chunk filled with air.
+    pub fn empty() -> Self {
+        Self {
+            blocks: [BlockId::Air; Self::SIZE.x * Self::SIZE.y * Self::SIZE.z],
+            light: [0; Self::SIZE.x * Self::SIZE.y * Self::SIZE.z],
+        }
+    }
+
     pub fn flat() -> Self {
         let mut blocks = [BlockId::Air; Self::SIZE.x * Self::SIZE.y * Self::SIZE.z];
         for x in 0..Self::SIZE.x {
@@ -24,7 +34,10 @@ impl Chunk {
                 }
             }
         }
-        Self { blocks }
+        Self {
+            blocks,
+            light: [0; Self::SIZE.x * Self::SIZE.y * Self::SIZE.z],
+        }
     }
 
     pub fn index(pos: Vec3<i32>) -> Option<usize> {
@@ -39,6 +52,33 @@ impl Chunk {
         Self::index(pos).map(|index| self.blocks[index])
     }
 
+    /// Replaces the block at `pos`, returning the previous one.
+    ///
+    /// Light is not updated, see [`Terrain::set_block`](crate::terrain::Terrain::set_block).
+    pub fn set(&mut self, pos: Vec3<i32>, block: BlockId) -> Option<BlockId> {
+        Self::index(pos).map(|index| std::mem::replace(&mut self.blocks[index], block))
+    }
+
+    pub fn sky_light(&self, pos: Vec3<i32>) -> Option<u8> {
+        Self::index(pos).map(|index| self.light[index] >> 4)
+    }
+
+    pub fn block_light(&self, pos: Vec3<i32>) -> Option<u8> {
+        Self::index(pos).map(|index| self.light[index] & 0xf)
+    }
+
+    pub fn set_sky_light(&mut self, pos: Vec3<i32>, level: u8) {
+        if let Some(index) = Self::index(pos) {
+            self.light[index] = (self.light[index] & 0xf) | (level.min(15) << 4);
+        }
+    }
+
+    pub fn set_block_light(&mut self, pos: Vec3<i32>, level: u8) {
+        if let Some(index) = Self::index(pos) {
+            self.light[index] = (self.light[index] & 0xf0) | level.min(15);
+        }
+    }
+
     pub fn out_of_bounds(pos: Vec3<i32>) -> bool {
         pos.is_any_negative()
             || pos.x >= Self::SIZE.x as i32
@@ -101,6 +141,21 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn light_nibbles_test() {
+        let mut chunk = Chunk::flat();
+        let pos = Vec3::new(3, 40, 7);
+        chunk.set_sky_light(pos, 12);
+        chunk.set_block_light(pos, 5);
+        assert_eq!(chunk.sky_light(pos), Some(12));
+        assert_eq!(chunk.block_light(pos), Some(5));
+
+        chunk.set_sky_light(pos, 0);
+        assert_eq!(chunk.sky_light(pos), Some(0));
+        assert_eq!(chunk.block_light(pos), Some(5));
+        assert_eq!(chunk.sky_light(Vec3::new(0, -1, 0)), None);
+    }
+
     #[test]
     fn out_of_bounds_test() {
         assert!(!Chunk::out_of_bounds(Vec3::zero()));
@@ -0,0 +1,171 @@
+/// Keeps track of the in-game time of day.
+#[derive(Debug, Clone)]
+pub struct WorldClock {
+    /// Elapsed in-game time, in days.
+    time: f64,
+    /// How many real seconds a full day lasts at normal speed.
+    day_length: f32,
+    /// Multiplier applied to the passage of time.
+    speed: f32,
+    paused: bool,
+}
+
+impl Default for WorldClock {
+    fn default() -> Self {
+        Self {
+            // Start at morning.
+            time: 0.3,
+            day_length: Self::DEFAULT_DAY_LENGTH,
+            speed: 1.0,
+            paused: false,
+        }
+    }
+}
+
+impl WorldClock {
+    /// Twenty minutes per day.
+    pub const DEFAULT_DAY_LENGTH: f32 = 1200.0;
+
+    /// Advances the clock by `dt` real seconds.
+    pub fn tick(&mut self, dt: f32) {
+        if self.paused || self.day_length <= 0.0 {
+            return;
+        }
+        self.time += (dt * self.speed / self.day_length) as f64;
+    }
+
+    /// The time of day in the `[0, 1)` range, where `0.0` is midnight,
+    /// `0.25` sunrise, `0.5` noon and `0.75` sunset.
+    pub fn time_of_day(&self) -> f32 {
+        self.time.rem_euclid(1.0) as f32
+    }
+
+    pub fn set_time_of_day(&mut self, time_of_day: f32) {
+        self.time = self.time.floor() + time_of_day.rem_euclid(1.0) as f64;
+    }
+
+    /// The number of full days that have passed.
+    pub fn day(&self) -> u64 {
+        self.time.max(0.0).floor() as u64
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Sets the time multiplier. Negative values are clamped to zero.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.0);
+    }
+
+    pub fn day_length(&self) -> f32 {
+        self.day_length
+    }
+
+    pub fn set_day_length(&mut self, seconds: f32) {
+        self.day_length = seconds;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn toggle_paused(&mut self) {
+        self.paused = !self.paused;
+    }
+}
+
+/// Splits the time between frames into ticks of a fixed duration, so that the
+/// simulation behaves the same whatever the frame rate.
+#[derive(Debug, Clone)]
+pub struct FixedTimestep {
+    /// The duration of a tick, in seconds.
+    step: f64,
+    /// Time that has passed but is not enough for a whole tick yet.
+    accumulator: f64,
+}
+
+impl FixedTimestep {
+    /// Frames longer than this, for example after the window was dragged, only
+    /// advance the simulation by this much instead of running a burst of ticks.
+    pub const MAX_FRAME_TIME: f32 = 0.25;
+
+    /// Runs `rate` ticks per second.
+    pub fn new(rate: f32) -> Self {
+        Self {
+            step: 1.0 / rate as f64,
+            accumulator: 0.0,
+        }
+    }
+
+    /// The duration of a tick, in seconds.
+    pub fn step(&self) -> f32 {
+        self.step as f32
+    }
+
+    /// Adds `dt` seconds to the time to simulate, and returns how many ticks to run.
+    pub fn advance(&mut self, dt: f32) -> u32 {
+        self.accumulator += dt.clamp(0.0, Self::MAX_FRAME_TIME) as f64;
+        let ticks = (self.accumulator / self.step).floor();
+        self.accumulator -= ticks * self.step;
+        ticks as u32
+    }
+
+    /// How far the simulation is between the last tick and the next one, from 0 to 1.
+    ///
+    /// Used to interpolate what is drawn between the states of the last two ticks.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.step).clamp(0.0, 1.0) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FixedTimestep, WorldClock};
+
+    #[test]
+    fn clock_advances_and_wraps() {
+        let mut clock = WorldClock::default();
+        clock.set_day_length(10.0);
+        clock.set_time_of_day(0.0);
+
+        clock.tick(5.0);
+        assert!((clock.time_of_day() - 0.5).abs() < 1e-5);
+
+        clock.tick(7.5);
+        assert!((clock.time_of_day() - 0.25).abs() < 1e-5);
+        assert_eq!(clock.day(), 1);
+    }
+
+    #[test]
+    fn paused_clock_does_not_advance() {
+        let mut clock = WorldClock::default();
+        let before = clock.time_of_day();
+        clock.set_paused(true);
+        clock.tick(100.0);
+        assert_eq!(clock.time_of_day(), before);
+
+        clock.toggle_paused();
+        clock.set_speed(2.0);
+        clock.tick(1.0);
+        let expected = before + 2.0 / WorldClock::DEFAULT_DAY_LENGTH;
+        assert!((clock.time_of_day() - expected).abs() < 1e-5);
+    }
+
+    #[test]
+    fn fixed_timestep_test() {
+        let mut timestep = FixedTimestep::new(20.0);
+        assert_eq!(timestep.advance(0.01), 0);
+        assert!((timestep.alpha() - 0.2).abs() < 1e-5);
+        assert_eq!(timestep.advance(0.115), 2);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-5);
+
+        // Long frames are cut short.
+        assert_eq!(timestep.advance(10.0), 5);
+        assert_eq!(timestep.advance(-1.0), 0);
+    }
+}
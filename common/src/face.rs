@@ -0,0 +1,51 @@
+use vek::Vec3;
+
+/// One of the six faces of a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Face {
+    North,
+    South,
+    East,
+    West,
+    Top,
+    Bottom,
+}
+
+impl Face {
+    pub const ALL: [Face; 6] = [
+        Face::North,
+        Face::South,
+        Face::East,
+        Face::West,
+        Face::Top,
+        Face::Bottom,
+    ];
+
+    /// The outward unit normal of this face.
+    pub const fn normal(self) -> Vec3<i32> {
+        match self {
+            Face::North => Vec3::new(0, 0, 1),
+            Face::South => Vec3::new(0, 0, -1),
+            Face::East => Vec3::new(1, 0, 0),
+            Face::West => Vec3::new(-1, 0, 0),
+            Face::Top => Vec3::new(0, 1, 0),
+            Face::Bottom => Vec3::new(0, -1, 0),
+        }
+    }
+
+    /// The index of this face in [`Face::ALL`].
+    pub const fn index(self) -> u32 {
+        self as u32
+    }
+
+    pub const fn opposite(self) -> Face {
+        match self {
+            Face::North => Face::South,
+            Face::South => Face::North,
+            Face::East => Face::West,
+            Face::West => Face::East,
+            Face::Top => Face::Bottom,
+            Face::Bottom => Face::Top,
+        }
+    }
+}
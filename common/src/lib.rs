@@ -1,2 +1,8 @@
 pub mod block;
 pub mod chunk;
+pub mod clock;
+pub mod face;
+pub mod light;
+pub mod player;
+pub mod raycast;
+pub mod terrain;
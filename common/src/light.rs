@@ -0,0 +1,415 @@
+//! Flood-fill propagation of sky light and block light.
+//!
+//! Sky light enters the world from above and travels straight down without
+//! losing intensity until it hits an opaque block. Block light is emitted by
+//! blocks such as lamps. Both lose one level per block when spreading in any
+//! other direction.
+
+use std::collections::{HashSet, VecDeque};
+
+use vek::{Vec2, Vec3};
+
+use crate::{block::BlockId, chunk::Chunk, terrain::Terrain};
+
+/// The brightest light level.
+pub const MAX_LIGHT: u8 = 15;
+
+const NEIGHBOURS: [Vec3<i32>; 6] = [
+    Vec3::new(1, 0, 0),
+    Vec3::new(-1, 0, 0),
+    Vec3::new(0, 1, 0),
+    Vec3::new(0, -1, 0),
+    Vec3::new(0, 0, 1),
+    Vec3::new(0, 0, -1),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Channel {
+    Sky,
+    Block,
+}
+
+impl Channel {
+    fn get(self, terrain: &Terrain, pos: Vec3<i32>) -> Option<u8> {
+        let chunk = terrain.chunk(Terrain::chunk_pos(pos))?;
+        let local = Terrain::local_pos(pos);
+        match self {
+            Channel::Sky => chunk.sky_light(local),
+            Channel::Block => chunk.block_light(local),
+        }
+    }
+
+    fn set(self, terrain: &mut Terrain, pos: Vec3<i32>, level: u8) {
+        if let Some(chunk) = terrain.chunk_mut(Terrain::chunk_pos(pos)) {
+            let local = Terrain::local_pos(pos);
+            match self {
+                Channel::Sky => chunk.set_sky_light(local, level),
+                Channel::Block => chunk.set_block_light(local, level),
+            }
+        }
+    }
+
+    /// The level that spreads from a block with light `level` to its neighbour at `offset`.
+    fn spread(self, level: u8, offset: Vec3<i32>) -> u8 {
+        if self == Channel::Sky && level == MAX_LIGHT && offset == -Vec3::unit_y() {
+            MAX_LIGHT
+        } else {
+            level.saturating_sub(1)
+        }
+    }
+}
+
+fn is_transparent(terrain: &Terrain, pos: Vec3<i32>) -> bool {
+    terrain.get(pos).is_some_and(|block| !block.is_opaque())
+}
+
+/// Computes the light of every loaded chunk from scratch.
+pub(crate) fn compute(terrain: &mut Terrain) {
+    let mut sky = VecDeque::new();
+    let mut block = VecDeque::new();
+
+    let positions = terrain.chunks().map(|(pos, _)| pos).collect::<Vec<_>>();
+    for chunk_pos in positions {
+        seed_chunk(terrain, chunk_pos, &mut sky, &mut block);
+    }
+
+    let mut dirty = HashSet::new();
+    propagate(terrain, Channel::Sky, sky, &mut dirty);
+    propagate(terrain, Channel::Block, block, &mut dirty);
+}
+
+/// Computes the light of newly inserted `chunks`, and spreads light across their
+/// borders in both directions.
+///
+/// Returns the chunks whose light changed, including neighbours that border a
+/// changed block.
+pub(crate) fn add_chunks(terrain: &mut Terrain, chunks: &[Vec2<i32>]) -> HashSet<Vec2<i32>> {
+    let mut sky = VecDeque::new();
+    let mut block = VecDeque::new();
+
+    for &chunk_pos in chunks {
+        seed_chunk(terrain, chunk_pos, &mut sky, &mut block);
+
+        // The blocks just outside the chunk spread their light into it.
+        let size = Chunk::SIZE.map(|x| x as i32);
+        let border = (0..size.x)
+            .flat_map(|x| [Vec2::new(x, -1), Vec2::new(x, size.z)])
+            .chain((0..size.z).flat_map(|z| [Vec2::new(-1, z), Vec2::new(size.x, z)]));
+        for column in border {
+            for y in 0..size.y {
+                let pos = Terrain::world_pos(chunk_pos, Vec3::new(column.x, y, column.y));
+                if !chunks.contains(&Terrain::chunk_pos(pos)) {
+                    sky.push_back(pos);
+                    block.push_back(pos);
+                }
+            }
+        }
+    }
+
+    let mut dirty = HashSet::new();
+    propagate(terrain, Channel::Sky, sky, &mut dirty);
+    propagate(terrain, Channel::Block, block, &mut dirty);
+    dirty
+}
+
+/// Resets the light of the chunk at `chunk_pos` to the sky light falling straight
+/// down and the light emitted by its blocks, and queues the lit positions.
+fn seed_chunk(
+    terrain: &mut Terrain,
+    chunk_pos: Vec2<i32>,
+    sky: &mut VecDeque<Vec3<i32>>,
+    block: &mut VecDeque<Vec3<i32>>,
+) {
+    let Some(chunk) = terrain.chunk_mut(chunk_pos) else {
+        return;
+    };
+    for x in 0..Chunk::SIZE.x as i32 {
+        for z in 0..Chunk::SIZE.z as i32 {
+            // Walk every column down from the top of the world.
+            let mut sky_level = MAX_LIGHT;
+            for y in (0..Chunk::SIZE.y as i32).rev() {
+                let local = Vec3::new(x, y, z);
+                let id = chunk.get(local).expect("position is inside the chunk");
+                if id.is_opaque() {
+                    sky_level = 0;
+                }
+                chunk.set_sky_light(local, sky_level);
+                chunk.set_block_light(local, id.light_emission());
+
+                let pos = Terrain::world_pos(chunk_pos, local);
+                if sky_level > 0 {
+                    sky.push_back(pos);
+                }
+                if id.light_emission() > 0 {
+                    block.push_back(pos);
+                }
+            }
+        }
+    }
+}
+
+/// Updates the light after the block at `pos` changed from `old` to `new`.
+///
+/// Returns the chunks whose light changed, including neighbours that
+/// border a changed block.
+pub(crate) fn update(
+    terrain: &mut Terrain,
+    pos: Vec3<i32>,
+    old: BlockId,
+    new: BlockId,
+) -> HashSet<Vec2<i32>> {
+    let mut dirty = HashSet::new();
+    if old == new {
+        return dirty;
+    }
+
+    for channel in [Channel::Sky, Channel::Block] {
+        let mut relight = VecDeque::new();
+
+        // Clear the light that went through the old block, and anything it lit.
+        let old_level = channel.get(terrain, pos).unwrap_or(0);
+        channel.set(terrain, pos, 0);
+        if old_level > 0 {
+            let removal = VecDeque::from([(pos, old_level)]);
+            unpropagate(terrain, channel, removal, &mut relight, &mut dirty);
+        }
+
+        // Then light up the new block.
+        match channel {
+            Channel::Sky if !new.is_opaque() && pos.y == Chunk::SIZE.y as i32 - 1 => {
+                channel.set(terrain, pos, MAX_LIGHT);
+                relight.push_back(pos);
+            }
+            Channel::Block if new.light_emission() > 0 => {
+                channel.set(terrain, pos, new.light_emission());
+                relight.push_back(pos);
+            }
+            _ => (),
+        }
+        if !new.is_opaque() {
+            relight.extend(NEIGHBOURS.iter().map(|offset| pos + offset));
+        }
+        propagate(terrain, channel, relight, &mut dirty);
+    }
+    dirty
+}
+
+/// Spreads light outwards from every position in `queue`.
+fn propagate(
+    terrain: &mut Terrain,
+    channel: Channel,
+    mut queue: VecDeque<Vec3<i32>>,
+    dirty: &mut HashSet<Vec2<i32>>,
+) {
+    while let Some(pos) = queue.pop_front() {
+        let Some(level) = channel.get(terrain, pos) else {
+            continue;
+        };
+        if level <= 1 {
+            continue;
+        }
+        for offset in NEIGHBOURS {
+            let neighbour = pos + offset;
+            if !is_transparent(terrain, neighbour) {
+                continue;
+            }
+            let spread = channel.spread(level, offset);
+            if channel.get(terrain, neighbour).is_some_and(|l| l < spread) {
+                channel.set(terrain, neighbour, spread);
+                dirty.extend(Terrain::touching_chunks(neighbour));
+                queue.push_back(neighbour);
+            }
+        }
+    }
+}
+
+/// Removes the light that originated from the positions in `queue`.
+///
+/// Positions lit by other sources are pushed into `relight` so that their
+/// light can fill the darkened area again.
+fn unpropagate(
+    terrain: &mut Terrain,
+    channel: Channel,
+    mut queue: VecDeque<(Vec3<i32>, u8)>,
+    relight: &mut VecDeque<Vec3<i32>>,
+    dirty: &mut HashSet<Vec2<i32>>,
+) {
+    while let Some((pos, level)) = queue.pop_front() {
+        dirty.extend(Terrain::touching_chunks(pos));
+        for offset in NEIGHBOURS {
+            let neighbour = pos + offset;
+            let Some(neighbour_level) = channel.get(terrain, neighbour) else {
+                continue;
+            };
+            if neighbour_level == 0 {
+                continue;
+            }
+
+            if neighbour_level < level || channel.spread(level, offset) == neighbour_level {
+                channel.set(terrain, neighbour, 0);
+                queue.push_back((neighbour, neighbour_level));
+
+                // Emitters keep their own light.
+                let emission = terrain.get(neighbour).map_or(0, BlockId::light_emission);
+                if channel == Channel::Block && emission > 0 {
+                    channel.set(terrain, neighbour, emission);
+                    relight.push_back(neighbour);
+                }
+            } else {
+                relight.push_back(neighbour);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vek::{Vec2, Vec3};
+
+    use super::MAX_LIGHT;
+    use crate::{block::BlockId, chunk::Chunk, terrain::Terrain};
+
+    /// A single chunk filled with stone up to `height`.
+    fn terrain(height: i32) -> Terrain {
+        let mut chunk = Chunk::empty();
+        for pos in chunk.iter_pos() {
+            if pos.y < height {
+                chunk.set(pos, BlockId::Stone);
+            }
+        }
+        let mut terrain = Terrain::new();
+        terrain.insert(Vec2::zero(), chunk);
+        terrain.compute_light();
+        terrain
+    }
+
+    #[test]
+    fn sky_light_test() {
+        let mut terrain = terrain(10);
+        assert_eq!(terrain.sky_light(Vec3::new(4, 10, 4)), MAX_LIGHT);
+        assert_eq!(terrain.sky_light(Vec3::new(4, 9, 4)), 0);
+
+        // An overhang keeps the sky from reaching the ground directly below it.
+        for x in 2..=6 {
+            for z in 2..=6 {
+                terrain.set_block(Vec3::new(x, 12, z), BlockId::Stone);
+            }
+        }
+        assert_eq!(terrain.sky_light(Vec3::new(4, 10, 4)), MAX_LIGHT - 3);
+        assert_eq!(terrain.sky_light(Vec3::new(2, 10, 2)), MAX_LIGHT - 1);
+        assert_eq!(terrain.sky_light(Vec3::new(4, 13, 4)), MAX_LIGHT);
+
+        // Removing it lets the light back in.
+        for x in 2..=6 {
+            for z in 2..=6 {
+                terrain.set_block(Vec3::new(x, 12, z), BlockId::Air);
+            }
+        }
+        assert_eq!(terrain.sky_light(Vec3::new(4, 10, 4)), MAX_LIGHT);
+    }
+
+    #[test]
+    fn digging_lets_sky_light_in() {
+        let mut terrain = terrain(10);
+        let hole = Vec3::new(8, 9, 8);
+        assert_eq!(terrain.sky_light(hole), 0);
+        terrain.set_block(hole, BlockId::Air);
+        assert_eq!(terrain.sky_light(hole), MAX_LIGHT);
+
+        // The side of the hole stays dark, light only leaks one block sideways.
+        terrain.set_block(hole - Vec3::unit_y(), BlockId::Air);
+        terrain.set_block(hole - Vec3::unit_y() + Vec3::unit_x(), BlockId::Air);
+        assert_eq!(terrain.sky_light(hole - Vec3::unit_y()), MAX_LIGHT);
+        assert_eq!(
+            terrain.sky_light(hole - Vec3::unit_y() + Vec3::unit_x()),
+            MAX_LIGHT - 1
+        );
+    }
+
+    #[test]
+    fn block_light_test() {
+        let mut terrain = terrain(10);
+        let lamp = Vec3::new(8, 10, 8);
+        let emission = BlockId::Lamp.light_emission();
+
+        let dirty = terrain.set_block(lamp, BlockId::Lamp).unwrap();
+        assert!(dirty.contains(&Vec2::zero()));
+        assert_eq!(terrain.block_light(lamp), emission);
+        assert_eq!(terrain.block_light(lamp + Vec3::unit_y()), emission - 1);
+        assert_eq!(terrain.block_light(lamp + Vec3::new(3, 0, 0)), emission - 3);
+        // Light does not go through stone.
+        assert_eq!(terrain.block_light(lamp - Vec3::unit_y()), 0);
+
+        // Walls block the light, it has to go around them.
+        terrain.set_block(lamp + Vec3::unit_x(), BlockId::Stone);
+        assert_eq!(terrain.block_light(lamp + Vec3::new(2, 0, 0)), emission - 4);
+
+        terrain.set_block(lamp, BlockId::Air);
+        assert_eq!(terrain.block_light(lamp), 0);
+        assert_eq!(terrain.block_light(lamp + Vec3::new(2, 0, 0)), 0);
+        assert_eq!(terrain.block_light(lamp + Vec3::new(0, 5, 0)), 0);
+    }
+
+    #[test]
+    fn overlapping_block_light_test() {
+        let mut terrain = terrain(10);
+        let a = Vec3::new(2, 10, 8);
+        let b = Vec3::new(8, 10, 8);
+        let emission = BlockId::Lamp.light_emission();
+        terrain.set_block(a, BlockId::Lamp);
+        terrain.set_block(b, BlockId::Lamp);
+
+        // Removing one lamp leaves the light of the other one.
+        terrain.set_block(b, BlockId::Air);
+        assert_eq!(terrain.block_light(a + Vec3::unit_x()), emission - 1);
+        assert_eq!(terrain.block_light(b), emission - 6);
+        assert_eq!(terrain.block_light(b + Vec3::unit_x()), emission - 7);
+    }
+
+    #[test]
+    fn added_chunk_light_test() {
+        let mut terrain = terrain(10);
+        let lamp = Vec3::new(15, 10, 8);
+        terrain.set_block(lamp, BlockId::Lamp);
+
+        // A chunk added next to the lamp is lit by it, and its own lamp lights the
+        // chunk that was already there.
+        let chunk = || {
+            let mut chunk = Chunk::empty();
+            for pos in chunk.iter_pos() {
+                if pos.y < 10 {
+                    chunk.set(pos, BlockId::Stone);
+                }
+            }
+            chunk.set(Vec3::new(1, 10, 2), BlockId::Lamp);
+            chunk
+        };
+        let added = Vec2::new(1, 0);
+        terrain.insert(added, chunk());
+        let dirty = terrain.light_chunks(&[added]);
+        assert!(dirty.contains(&added));
+        assert!(dirty.contains(&Vec2::zero()));
+
+        // It ends up the same as lighting both chunks from scratch.
+        let mut expected = self::terrain(10);
+        expected.set_block(lamp, BlockId::Lamp);
+        expected.insert(added, chunk());
+        expected.compute_light();
+        for chunk_pos in [Vec2::zero(), added] {
+            for local in Chunk::empty().iter_pos() {
+                let pos = Terrain::world_pos(chunk_pos, local);
+                assert_eq!(terrain.sky_light(pos), expected.sky_light(pos), "{}", pos);
+                assert_eq!(
+                    terrain.block_light(pos),
+                    expected.block_light(pos),
+                    "{}",
+                    pos
+                );
+            }
+        }
+        assert_eq!(
+            terrain.block_light(lamp + Vec3::unit_x()),
+            BlockId::Lamp.light_emission() - 1
+        );
+    }
+}
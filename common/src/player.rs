@@ -0,0 +1,430 @@
+use vek::{Aabb, Vec3};
+
+use crate::terrain::Terrain;
+
+/// How the player moves around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MoveMode {
+    /// Walks on the ground, pulled down by gravity.
+    #[default]
+    Walk,
+    /// Flies in every direction, without gravity.
+    Fly,
+}
+
+/// What the player wants to do during a tick.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PlayerInput {
+    /// The velocity the player wants to move at, in world space. The vertical
+    /// part is only used when flying.
+    pub velocity: Vec3<f32>,
+    /// Whether to jump, which only happens when walking on the ground.
+    pub jump: bool,
+}
+
+/// Someone moving around the world, colliding with its blocks.
+#[derive(Debug, Clone)]
+pub struct Player {
+    /// The position of the bottom centre of the player.
+    pub pos: Vec3<f32>,
+    /// The velocity in blocks per second.
+    pub velocity: Vec3<f32>,
+    /// How fast the player speeds up towards the velocity it wants to move at,
+    /// in blocks per second squared.
+    pub acceleration: f32,
+    /// How fast the player slows down when it does not want to move, in blocks
+    /// per second squared.
+    pub deceleration: f32,
+    mode: MoveMode,
+    on_ground: bool,
+}
+
+/// How much closer than touching a block the player can get before colliding,
+/// so that standing on a block or against a wall does not count as being inside it.
+const EPSILON: f32 = 1e-4;
+
+impl Player {
+    pub const WIDTH: f32 = 0.6;
+    pub const HEIGHT: f32 = 1.8;
+    /// The height of the eyes above the bottom of the player.
+    pub const EYE_HEIGHT: f32 = 1.62;
+    /// In blocks per second squared.
+    pub const GRAVITY: f32 = 32.0;
+    /// The vertical speed given by a jump, enough to get on top of a block.
+    pub const JUMP_SPEED: f32 = 9.0;
+    /// The falling speed can not grow past this, in blocks per second.
+    pub const MAX_FALL_SPEED: f32 = 60.0;
+    /// How high a ledge can be to be walked onto without jumping. Blocks are
+    /// full cubes, so one block high ledges are climbed automatically.
+    pub const STEP_HEIGHT: f32 = 1.0;
+
+    pub fn new(pos: Vec3<f32>) -> Self {
+        Self {
+            pos,
+            velocity: Vec3::zero(),
+            acceleration: f32::INFINITY,
+            deceleration: f32::INFINITY,
+            mode: MoveMode::default(),
+            on_ground: false,
+        }
+    }
+
+    pub fn mode(&self) -> MoveMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: MoveMode) {
+        if mode != self.mode {
+            self.mode = mode;
+            self.velocity.y = 0.0;
+            self.on_ground = false;
+        }
+    }
+
+    /// Whether the player stood on a block at the end of the last tick.
+    pub fn on_ground(&self) -> bool {
+        self.on_ground
+    }
+
+    /// The position of the eyes, where the camera goes.
+    pub fn eye_pos(&self) -> Vec3<f32> {
+        self.pos + Vec3::unit_y() * Self::EYE_HEIGHT
+    }
+
+    /// The box taken up by the player.
+    pub fn aabb(&self) -> Aabb<f32> {
+        let half = Vec3::new(Self::WIDTH / 2.0, 0.0, Self::WIDTH / 2.0);
+        Aabb {
+            min: self.pos - half,
+            max: self.pos + half + Vec3::unit_y() * Self::HEIGHT,
+        }
+    }
+
+    /// Whether the block at `pos` overlaps the player.
+    pub fn intersects_block(&self, pos: Vec3<i32>) -> bool {
+        let aabb = self.aabb();
+        (0..3).all(|axis| {
+            let range = blocks(aabb.min[axis], aabb.max[axis]);
+            range.contains(&pos[axis])
+        })
+    }
+
+    /// Moves the player for `dt` seconds, stopping against the blocks for which `is_solid` is true.
+    pub fn tick(&mut self, dt: f32, input: PlayerInput, is_solid: impl Fn(Vec3<i32>) -> bool) {
+        if dt <= 0.0 {
+            return;
+        }
+        self.accelerate(dt, input.velocity);
+        match self.mode {
+            MoveMode::Walk => {
+                if input.jump && self.on_ground {
+                    self.velocity.y = Self::JUMP_SPEED;
+                }
+                self.velocity.y = (self.velocity.y - Self::GRAVITY * dt).max(-Self::MAX_FALL_SPEED);
+            }
+            MoveMode::Fly => (),
+        }
+
+        let offset = self.velocity * dt;
+        let was_on_ground = self.on_ground;
+        self.move_horizontally(offset, was_on_ground, &is_solid);
+
+        let moved = self.move_axis(1, offset.y, &is_solid);
+        let blocked = moved != offset.y;
+        if blocked {
+            self.velocity.y = 0.0;
+        }
+        self.on_ground = self.mode == MoveMode::Walk && blocked && offset.y < 0.0;
+    }
+
+    /// Moves the player by `dt` seconds with the terrain, where blocks outside of
+    /// the loaded chunks are walked through.
+    ///
+    /// The bottom of the loaded chunks is solid, so digging through it doesn't
+    /// drop the player out of the world.
+    pub fn tick_in(&mut self, dt: f32, input: PlayerInput, terrain: &Terrain) {
+        self.tick(dt, input, |pos| {
+            let loaded = terrain.chunk(Terrain::chunk_pos(pos)).is_some();
+            (loaded && pos.y < 0) || terrain.is_solid(pos)
+        });
+    }
+
+    /// Speeds up or slows down towards `target` at a constant rate.
+    fn accelerate(&mut self, dt: f32, mut target: Vec3<f32>) {
+        let mut velocity = self.velocity;
+        if self.mode == MoveMode::Walk {
+            // Gravity takes care of the vertical speed.
+            target.y = 0.0;
+            velocity.y = 0.0;
+        }
+        let rate = if target == Vec3::zero() {
+            self.deceleration
+        } else {
+            self.acceleration
+        };
+        let diff = target - velocity;
+        let max_change = rate * dt;
+        velocity = if diff.magnitude() <= max_change {
+            target
+        } else {
+            velocity + diff.normalized() * max_change
+        };
+        if self.mode == MoveMode::Walk {
+            velocity.y = self.velocity.y;
+        }
+        self.velocity = velocity;
+    }
+
+    /// Moves along x and z, sliding along walls and stepping onto low ledges.
+    fn move_horizontally(
+        &mut self,
+        offset: Vec3<f32>,
+        can_step: bool,
+        is_solid: &impl Fn(Vec3<i32>) -> bool,
+    ) {
+        let start = self.pos;
+        let x = self.move_axis(0, offset.x, is_solid);
+        let z = self.move_axis(2, offset.z, is_solid);
+        if x == offset.x && z == offset.z {
+            return;
+        }
+
+        if can_step {
+            // Try again from higher up, and keep that if it got further.
+            let blocked = self.pos;
+            self.pos = start;
+            let up = self.move_axis(1, Self::STEP_HEIGHT, is_solid);
+            let stepped_x = self.move_axis(0, offset.x, is_solid);
+            let stepped_z = self.move_axis(2, offset.z, is_solid);
+            self.move_axis(1, -up, is_solid);
+            let distance = |pos: Vec3<f32>| {
+                let moved = pos - start;
+                moved.x * moved.x + moved.z * moved.z
+            };
+            if distance(self.pos) > distance(blocked) + EPSILON {
+                self.stop_blocked(offset, stepped_x, stepped_z);
+                return;
+            }
+            self.pos = blocked;
+        }
+        self.stop_blocked(offset, x, z);
+    }
+
+    /// Stops moving along the horizontal axes on which a wall was hit.
+    fn stop_blocked(&mut self, offset: Vec3<f32>, x: f32, z: f32) {
+        if x != offset.x {
+            self.velocity.x = 0.0;
+        }
+        if z != offset.z {
+            self.velocity.z = 0.0;
+        }
+    }
+
+    /// Moves up to `amount` along `axis`, stopping before the first solid block in the way.
+    ///
+    /// Every block swept through is checked, so fast moves can't skip over thin walls.
+    /// Returns how far the player actually moved.
+    fn move_axis(
+        &mut self,
+        axis: usize,
+        amount: f32,
+        is_solid: &impl Fn(Vec3<i32>) -> bool,
+    ) -> f32 {
+        if amount == 0.0 {
+            return 0.0;
+        }
+        let aabb = self.aabb();
+        // The blocks overlapped on the two other axes.
+        let others = [(axis + 1) % 3, (axis + 2) % 3];
+        let [a, b] = others.map(|other| blocks(aabb.min[other], aabb.max[other]));
+        let layer_solid = |layer: i32| {
+            a.clone().any(|i| {
+                b.clone().any(|j| {
+                    let mut pos = Vec3::zero();
+                    pos[axis] = layer;
+                    pos[others[0]] = i;
+                    pos[others[1]] = j;
+                    is_solid(pos)
+                })
+            })
+        };
+
+        let mut moved = amount;
+        if amount > 0.0 {
+            let first = (aabb.max[axis] - EPSILON).floor() as i32 + 1;
+            let last = (aabb.max[axis] + amount - EPSILON).floor() as i32;
+            if let Some(layer) = (first..=last).find(|&layer| layer_solid(layer)) {
+                moved = (layer as f32 - aabb.max[axis]).max(0.0);
+            }
+        } else {
+            let first = (aabb.min[axis] + EPSILON).floor() as i32 - 1;
+            let last = (aabb.min[axis] + amount + EPSILON).floor() as i32;
+            if let Some(layer) = (last..=first).rev().find(|&layer| layer_solid(layer)) {
+                moved = (layer as f32 + 1.0 - aabb.min[axis]).min(0.0);
+            }
+        }
+        self.pos[axis] += moved;
+        moved
+    }
+}
+
+/// The blocks overlapped by the range from `min` to `max` on one axis, without
+/// the ones it only touches.
+fn blocks(min: f32, max: f32) -> std::ops::RangeInclusive<i32> {
+    (min + EPSILON).floor() as i32..=(max - EPSILON).floor() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use vek::{Vec2, Vec3};
+
+    use super::{MoveMode, Player, PlayerInput};
+    use crate::{chunk::Chunk, terrain::Terrain};
+
+    /// A floor at y = 0, with a few more blocks on top of it.
+    fn world(blocks: &[[i32; 3]]) -> impl Fn(Vec3<i32>) -> bool {
+        let blocks = blocks
+            .iter()
+            .map(|&pos| Vec3::from(pos))
+            .collect::<HashSet<_>>();
+        move |pos| pos.y < 1 || blocks.contains(&pos)
+    }
+
+    fn run(
+        player: &mut Player,
+        input: PlayerInput,
+        seconds: f32,
+        is_solid: &impl Fn(Vec3<i32>) -> bool,
+    ) {
+        for _ in 0..(seconds * 60.0) as u32 {
+            player.tick(1.0 / 60.0, input, is_solid);
+        }
+    }
+
+    #[test]
+    fn landing_test() {
+        let is_solid = world(&[]);
+        let mut player = Player::new(Vec3::new(0.5, 10.0, 0.5));
+        player.tick(0.1, PlayerInput::default(), &is_solid);
+        assert!(player.pos.y < 10.0);
+        assert!(!player.on_ground());
+
+        run(&mut player, PlayerInput::default(), 2.0, &is_solid);
+        assert_eq!(player.pos.y, 1.0);
+        assert!(player.on_ground());
+        assert_eq!(player.velocity, Vec3::zero());
+
+        // Falling fast doesn't go through the floor.
+        let mut player = Player::new(Vec3::new(0.5, 100.0, 0.5));
+        player.velocity.y = -Player::MAX_FALL_SPEED;
+        player.tick(2.0, PlayerInput::default(), &is_solid);
+        assert_eq!(player.pos.y, 1.0);
+
+        // Jumping gets on top of a block, but not of two.
+        let jump = PlayerInput {
+            jump: true,
+            ..Default::default()
+        };
+        player.tick(1.0 / 60.0, jump, &is_solid);
+        assert!(player.velocity.y > 0.0);
+        let mut highest: f32 = 0.0;
+        for _ in 0..60 {
+            player.tick(1.0 / 60.0, PlayerInput::default(), &is_solid);
+            highest = highest.max(player.pos.y);
+        }
+        assert!(highest > 2.0 && highest < 3.0, "{highest}");
+        assert_eq!(player.pos.y, 1.0);
+    }
+
+    #[test]
+    fn wall_sliding_test() {
+        // A wall along z at x = 2, two blocks high.
+        let wall = (-5..5)
+            .flat_map(|z| [[2, 1, z], [2, 2, z]])
+            .collect::<Vec<_>>();
+        let is_solid = world(&wall);
+        let mut player = Player::new(Vec3::new(0.5, 1.0, 0.5));
+        let input = PlayerInput {
+            velocity: Vec3::new(4.0, 0.0, 2.0),
+            jump: false,
+        };
+        run(&mut player, input, 1.0, &is_solid);
+
+        // Stopped against the wall, but kept moving along it.
+        assert!((player.aabb().max.x - 2.0).abs() < 1e-4);
+        assert!((player.pos.z - 2.5).abs() < 0.1, "{}", player.pos.z);
+        assert_eq!(player.velocity.x, 0.0);
+        assert_eq!(player.velocity.z, 2.0);
+        assert!(!player.intersects_block(Vec3::new(2, 1, 0)));
+        assert!(player.on_ground());
+    }
+
+    #[test]
+    fn step_up_test() {
+        let input = PlayerInput {
+            velocity: Vec3::new(4.0, 0.0, 0.0),
+            jump: false,
+        };
+
+        // Walks onto a ledge one block high.
+        let ledge = |pos: Vec3<i32>| pos.y < 1 || (pos.x >= 2 && pos.y < 2);
+        let mut player = Player::new(Vec3::new(0.5, 1.0, 0.5));
+        run(&mut player, input, 1.0, &ledge);
+        assert_eq!(player.pos.y, 2.0);
+        assert!(player.pos.x > 4.0);
+        assert!(player.on_ground());
+
+        // But not onto one two blocks high.
+        let wall = |pos: Vec3<i32>| pos.y < 1 || (pos.x >= 2 && pos.y < 3);
+        let mut player = Player::new(Vec3::new(0.5, 1.0, 0.5));
+        run(&mut player, input, 1.0, &wall);
+        assert!((player.aabb().max.x - 2.0).abs() < 1e-4);
+        assert_eq!(player.pos.y, 1.0);
+
+        // Nor while flying.
+        let mut player = Player::new(Vec3::new(0.5, 1.0, 0.5));
+        player.set_mode(MoveMode::Fly);
+        run(&mut player, input, 1.0, &ledge);
+        assert!((player.aabb().max.x - 2.0).abs() < 1e-4);
+        assert_eq!(player.pos.y, 1.0);
+    }
+
+    #[test]
+    fn world_bottom_test() {
+        // Nothing was left of the chunk, not even its bottom layer.
+        let mut terrain = Terrain::new();
+        terrain.insert(Vec2::zero(), Chunk::empty());
+        let mut player = Player::new(Vec3::new(8.5, 10.0, 8.5));
+        for _ in 0..120 {
+            player.tick_in(1.0 / 60.0, PlayerInput::default(), &terrain);
+        }
+        assert_eq!(player.pos.y, 0.0);
+        assert!(player.on_ground());
+
+        // Outside of the loaded chunks, there is nothing to stand on.
+        let mut player = Player::new(Vec3::new(-8.5, 10.0, 8.5));
+        for _ in 0..120 {
+            player.tick_in(1.0 / 60.0, PlayerInput::default(), &terrain);
+        }
+        assert!(player.pos.y < 0.0);
+    }
+
+    #[test]
+    fn fly_test() {
+        let is_solid = world(&[]);
+        let mut player = Player::new(Vec3::new(0.5, 10.0, 0.5));
+        player.set_mode(MoveMode::Fly);
+        let input = PlayerInput {
+            velocity: Vec3::new(0.0, -5.0, 0.0),
+            jump: false,
+        };
+        player.tick(0.1, PlayerInput::default(), &is_solid);
+        assert_eq!(player.pos.y, 10.0);
+        run(&mut player, input, 3.0, &is_solid);
+        // Flying still collides, but does not stand on the ground.
+        assert_eq!(player.pos.y, 1.0);
+        assert!(!player.on_ground());
+    }
+}
@@ -0,0 +1,190 @@
+use vek::Vec3;
+
+use crate::{face::Face, terrain::Terrain};
+
+/// The block a ray ran into.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RaycastHit {
+    /// The position of the block that was hit.
+    pub pos: Vec3<i32>,
+    /// The face of the block the ray entered through.
+    pub face: Face,
+    /// The distance from the origin of the ray to the hit point.
+    pub distance: f32,
+}
+
+impl RaycastHit {
+    /// The position of the block in front of the face that was hit, where a block would be placed.
+    pub fn adjacent(&self) -> Vec3<i32> {
+        self.pos + self.face.normal()
+    }
+}
+
+/// Walks the block grid along a ray, and returns the first block for which `is_solid` is true.
+///
+/// Every block the ray goes through is visited in order, using the algorithm from
+/// "A Fast Voxel Traversal Algorithm for Ray Tracing" (Amanatides and Woo). The block
+/// containing `origin` is skipped, so that a ray starting inside a block can still
+/// reach the blocks around it. Returns `None` if nothing is hit within `max_distance`.
+pub fn raycast(
+    origin: Vec3<f32>,
+    dir: Vec3<f32>,
+    max_distance: f32,
+    mut is_solid: impl FnMut(Vec3<i32>) -> bool,
+) -> Option<RaycastHit> {
+    let dir = dir.try_normalized()?;
+    let mut pos = origin.map(|x| x.floor() as i32);
+    let step = dir.map(|x| x.signum() as i32);
+    // The distance along the ray between two block boundaries, on every axis.
+    let delta = dir.map(|x| (1.0 / x).abs());
+    // The distance along the ray to the next block boundary, on every axis.
+    let mut next = Vec3::new(0, 1, 2).map(|axis| {
+        if dir[axis] == 0.0 {
+            return f32::INFINITY;
+        }
+        let offset = if dir[axis] > 0.0 {
+            pos[axis] as f32 + 1.0 - origin[axis]
+        } else {
+            origin[axis] - pos[axis] as f32
+        };
+        offset * delta[axis]
+    });
+
+    loop {
+        // Step into the next block through the closest boundary.
+        let axis = if next.x < next.y {
+            if next.x < next.z {
+                0
+            } else {
+                2
+            }
+        } else if next.y < next.z {
+            1
+        } else {
+            2
+        };
+        let distance = next[axis];
+        if distance > max_distance {
+            return None;
+        }
+        pos[axis] += step[axis];
+        next[axis] += delta[axis];
+
+        if is_solid(pos) {
+            let face = match (axis, step[axis] > 0) {
+                (0, true) => Face::West,
+                (0, false) => Face::East,
+                (1, true) => Face::Bottom,
+                (1, false) => Face::Top,
+                (_, true) => Face::South,
+                (_, false) => Face::North,
+            };
+            return Some(RaycastHit {
+                pos,
+                face,
+                distance,
+            });
+        }
+    }
+}
+
+impl Terrain {
+    /// Finds the first block that is not air along a ray, see [`raycast`].
+    ///
+    /// Blocks outside of the loaded terrain are treated as air.
+    pub fn raycast(
+        &self,
+        origin: Vec3<f32>,
+        dir: Vec3<f32>,
+        max_distance: f32,
+    ) -> Option<RaycastHit> {
+        raycast(origin, dir, max_distance, |pos| self.is_solid(pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vek::{Vec2, Vec3};
+
+    use crate::{block::BlockId, chunk::Chunk, face::Face, terrain::Terrain};
+
+    use super::raycast;
+
+    #[test]
+    fn raycast_axes_test() {
+        let solid = |target: Vec3<i32>| move |pos: Vec3<i32>| pos == target;
+        let origin = Vec3::new(0.5, 0.5, 0.5);
+
+        let cases = [
+            (Vec3::unit_x(), Vec3::new(3, 0, 0), Face::West),
+            (-Vec3::unit_x(), Vec3::new(-3, 0, 0), Face::East),
+            (Vec3::unit_y(), Vec3::new(0, 3, 0), Face::Bottom),
+            (-Vec3::unit_y(), Vec3::new(0, -3, 0), Face::Top),
+            (Vec3::unit_z(), Vec3::new(0, 0, 3), Face::South),
+            (-Vec3::unit_z(), Vec3::new(0, 0, -3), Face::North),
+        ];
+        for (dir, target, face) in cases {
+            let hit = raycast(origin, dir, 10.0, solid(target)).unwrap();
+            assert_eq!(hit.pos, target);
+            assert_eq!(hit.face, face);
+            assert!((hit.distance - 2.5).abs() < 1e-5);
+            assert_eq!(hit.adjacent(), target - dir.map(|x| x as i32));
+        }
+
+        // Too far away.
+        assert_eq!(
+            raycast(origin, Vec3::unit_x(), 2.0, solid(Vec3::new(3, 0, 0))),
+            None
+        );
+        // The block the ray starts in is ignored.
+        assert_eq!(
+            raycast(origin, Vec3::unit_x(), 10.0, solid(Vec3::zero())),
+            None
+        );
+        assert_eq!(raycast(origin, Vec3::zero(), 10.0, |_| true), None);
+        // Rays starting on a block boundary.
+        let hit = raycast(
+            Vec3::zero(),
+            Vec3::unit_x(),
+            10.0,
+            solid(Vec3::new(2, 0, 0)),
+        )
+        .unwrap();
+        assert_eq!(hit.distance, 2.0);
+    }
+
+    #[test]
+    fn raycast_diagonal_test() {
+        // Visits every block crossed by the ray, even when it only clips a corner.
+        let mut visited = vec![];
+        raycast(
+            Vec3::new(0.5, 0.5, 0.5),
+            Vec3::new(1.0, 0.3, 0.0),
+            2.0,
+            |pos| {
+                visited.push(pos);
+                false
+            },
+        );
+        assert_eq!(
+            visited,
+            [Vec3::new(1, 0, 0), Vec3::new(2, 0, 0), Vec3::new(2, 1, 0)]
+        );
+    }
+
+    #[test]
+    fn terrain_raycast_test() {
+        let mut terrain = Terrain::new();
+        terrain.insert(Vec2::zero(), Chunk::flat());
+        let ground = (0..Chunk::SIZE.y as i32)
+            .rev()
+            .find(|&y| terrain.get(Vec3::new(4, y, 4)) != Some(BlockId::Air))
+            .unwrap();
+
+        let origin = Vec3::new(4.5, ground as f32 + 3.5, 4.5);
+        let hit = terrain.raycast(origin, -Vec3::unit_y(), 8.0).unwrap();
+        assert_eq!(hit.pos, Vec3::new(4, ground, 4));
+        assert_eq!(hit.face, Face::Top);
+        assert_eq!(hit.adjacent(), Vec3::new(4, ground + 1, 4));
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::{HashMap, HashSet};
+
+use vek::{Vec2, Vec3};
+
+use crate::{block::BlockId, chunk::Chunk, light};
+
+/// The loaded chunks of the world, indexed by chunk position.
+#[derive(Default)]
+pub struct Terrain {
+    chunks: HashMap<Vec2<i32>, Chunk>,
+}
+
+impl Terrain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a chunk to the terrain. Light is not computed, see [`Terrain::light_chunks`].
+    pub fn insert(&mut self, pos: Vec2<i32>, chunk: Chunk) {
+        self.chunks.insert(pos, chunk);
+    }
+
+    pub fn remove(&mut self, pos: Vec2<i32>) -> Option<Chunk> {
+        self.chunks.remove(&pos)
+    }
+
+    pub fn chunk(&self, pos: Vec2<i32>) -> Option<&Chunk> {
+        self.chunks.get(&pos)
+    }
+
+    pub fn chunk_mut(&mut self, pos: Vec2<i32>) -> Option<&mut Chunk> {
+        self.chunks.get_mut(&pos)
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = (Vec2<i32>, &Chunk)> {
+        self.chunks.iter().map(|(pos, chunk)| (*pos, chunk))
+    }
+
+    /// The position of the chunk containing the world position `pos`.
+    pub fn chunk_pos(pos: Vec3<i32>) -> Vec2<i32> {
+        Vec2::new(
+            pos.x.div_euclid(Chunk::SIZE.x as i32),
+            pos.z.div_euclid(Chunk::SIZE.z as i32),
+        )
+    }
+
+    /// Converts a world position into a position local to its chunk.
+    pub fn local_pos(pos: Vec3<i32>) -> Vec3<i32> {
+        Vec3::new(
+            pos.x.rem_euclid(Chunk::SIZE.x as i32),
+            pos.y,
+            pos.z.rem_euclid(Chunk::SIZE.z as i32),
+        )
+    }
+
+    /// Converts a chunk position and a local position into a world position.
+    pub fn world_pos(chunk_pos: Vec2<i32>, local: Vec3<i32>) -> Vec3<i32> {
+        Vec3::new(
+            chunk_pos.x * Chunk::SIZE.x as i32 + local.x,
+            local.y,
+            chunk_pos.y * Chunk::SIZE.z as i32 + local.z,
+        )
+    }
+
+    /// Returns the block at the world position `pos`, if its chunk is loaded.
+    pub fn get(&self, pos: Vec3<i32>) -> Option<BlockId> {
+        self.chunk(Self::chunk_pos(pos))?.get(Self::local_pos(pos))
+    }
+
+    /// Whether there is a solid block at `pos`, blocks outside of the loaded terrain are not.
+    pub fn is_solid(&self, pos: Vec3<i32>) -> bool {
+        self.get(pos).is_some_and(BlockId::is_solid)
+    }
+
+    /// The sky light at `pos`.
+    ///
+    /// Positions above the world or in chunks that are not loaded
+    /// are fully lit, positions below the world are dark.
+    pub fn sky_light(&self, pos: Vec3<i32>) -> u8 {
+        if pos.y >= Chunk::SIZE.y as i32 {
+            return light::MAX_LIGHT;
+        }
+        if pos.y < 0 {
+            return 0;
+        }
+        self.chunk(Self::chunk_pos(pos))
+            .and_then(|chunk| chunk.sky_light(Self::local_pos(pos)))
+            .unwrap_or(light::MAX_LIGHT)
+    }
+
+    /// The block light at `pos`, or 0 if it is outside the loaded terrain.
+    pub fn block_light(&self, pos: Vec3<i32>) -> u8 {
+        self.chunk(Self::chunk_pos(pos))
+            .and_then(|chunk| chunk.block_light(Self::local_pos(pos)))
+            .unwrap_or(0)
+    }
+
+    /// Replaces the block at `pos` and updates the light around it.
+    ///
+    /// Returns the positions of the chunks whose mesh is affected by the change,
+    /// or `None` if `pos` is not inside the loaded terrain.
+    pub fn set_block(&mut self, pos: Vec3<i32>, block: BlockId) -> Option<HashSet<Vec2<i32>>> {
+        let old = self
+            .chunk_mut(Self::chunk_pos(pos))?
+            .set(Self::local_pos(pos), block)?;
+        let mut dirty = light::update(self, pos, old, block);
+        dirty.extend(Self::touching_chunks(pos));
+        Some(dirty)
+    }
+
+    /// Computes the sky and block light of every loaded chunk from scratch.
+    pub fn compute_light(&mut self) {
+        light::compute(self);
+    }
+
+    /// Computes the light of newly inserted `chunks`, and spreads light between them
+    /// and the chunks already loaded around them.
+    ///
+    /// Returns the positions of the chunks whose mesh is affected, including the
+    /// neighbours of the new chunks.
+    pub fn light_chunks(&mut self, chunks: &[Vec2<i32>]) -> HashSet<Vec2<i32>> {
+        let mut dirty = light::add_chunks(self, chunks);
+        for &pos in chunks {
+            dirty.extend(Self::neighbour_chunks(pos));
+        }
+        dirty
+    }
+
+    /// The chunk at `pos` and the four chunks sharing one of its sides.
+    pub fn neighbour_chunks(pos: Vec2<i32>) -> impl Iterator<Item = Vec2<i32>> {
+        [
+            Vec2::zero(),
+            Vec2::unit_x(),
+            -Vec2::unit_x(),
+            Vec2::unit_y(),
+            -Vec2::unit_y(),
+        ]
+        .into_iter()
+        .map(move |offset| pos + offset)
+    }
+
+    /// The chunk containing `pos` and the chunks sharing one of the faces of `pos`.
+    pub(crate) fn touching_chunks(pos: Vec3<i32>) -> impl Iterator<Item = Vec2<i32>> {
+        [
+            Vec3::zero(),
+            Vec3::unit_x(),
+            -Vec3::unit_x(),
+            Vec3::unit_z(),
+            -Vec3::unit_z(),
+        ]
+        .into_iter()
+        .map(move |offset| Self::chunk_pos(pos + offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vek::{Vec2, Vec3};
+
+    use super::Terrain;
+
+    #[test]
+    fn world_to_local_test() {
+        let pos = Vec3::new(-1, 10, 17);
+        assert_eq!(Terrain::chunk_pos(pos), Vec2::new(-1, 1));
+        assert_eq!(Terrain::local_pos(pos), Vec3::new(15, 10, 1));
+        assert_eq!(
+            Terrain::world_pos(Terrain::chunk_pos(pos), Terrain::local_pos(pos)),
+            pos
+        );
+    }
+}